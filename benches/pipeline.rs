@@ -0,0 +1,81 @@
+use std::iter::FromIterator;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use plumbum::*;
+use plumbum::extra::{chunk, count, filter, merge_n};
+
+const N: u64 = 1_000_000;
+
+fn source<'a>() -> Source<'a, u64> {
+    Source::from_iter(0..N)
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+
+    group.bench_function("count", |b| {
+        b.iter(|| black_box(source().connect(count())))
+    });
+
+    group.bench_function("filter_map_fold", |b| {
+        b.iter(|| {
+            let conduit = fuse!{
+                filter(|x| x % 2 == 0),
+                Conduit::transform(|x| x * 3),
+                Conduit::transform(|x| x + 1)
+            };
+            black_box(source().fuse(conduit).connect(Sink::fold(0, |x, y| x + y)))
+        })
+    });
+
+    group.bench_function("fuse_10", |b| {
+        b.iter(|| {
+            let src = fuse!{
+                source(),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x| x + 1),
+                Conduit::transform(|x: u64| x + 1)
+            };
+            black_box(src.connect(count()))
+        })
+    });
+
+    group.bench_function("chunk_256", |b| {
+        b.iter(|| black_box(source().fuse(chunk(256)).connect(count())))
+    });
+
+    // The time per map should stay the same as the chain grows.
+    for &n in &[1000, 2000, 4000, 8000] {
+        group.bench_function(&format!("map_chain_{}", n), |b| {
+            b.iter(|| {
+                let mut sink = consume().map(|x: Option<u64>| x.unwrap_or(0));
+                for _ in 0..n {
+                    sink = sink.map(|x| x + 1);
+                }
+                black_box(Source::from_iter(0..1).connect(sink))
+            })
+        });
+    }
+
+    group.bench_function("merge", |b| {
+        b.iter(|| {
+            let left = Source::from_iter(0..N / 2);
+            let right = Source::from_iter(N / 2..N);
+            black_box(merge_n(vec![left, right]).connect(count()))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,276 @@
+//! Derive macros for [`plumbum`](https://crates.io/crates/plumbum).
+//!
+//! `#[derive(Conduit)]` turns a struct describing a pipeline into a runnable one.
+//! The struct needs exactly one field marked `#[source]`, any number of fields
+//! marked `#[conduit]`, and exactly one field marked `#[sink]`, whose type is
+//! written as `Sink<.., R>`. The derive generates a method
+//! `fn run(self) -> R`, which fuses the source with the conduits in the order
+//! in which they are declared, and connects the result to the sink.
+//!
+//! ```rust,ignore
+//! use plumbum::{Conduit, Sink, Source};
+//!
+//! #[derive(Conduit)]
+//! struct Sum<'a> {
+//!     #[source] src: Source<'a, i32>,
+//!     #[conduit] double: Conduit<'a, i32, i32>,
+//!     #[sink] out: Sink<'a, i32, i32>
+//! }
+//!
+//! let sum = Sum {
+//!     src: Source::from(vec![1, 2]),
+//!     double: Conduit::transform(|x| x * 2),
+//!     out: Sink::fold(0, |x, y| x + y)
+//! };
+//! assert_eq!(sum.run(), 6);
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// Derives a `run` method for a pipeline struct, see the crate documentation.
+#[proc_macro_derive(Conduit, attributes(source, conduit, sink))]
+pub fn derive_conduit(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(output) => output,
+        Err(msg) => format!("compile_error!({:?});", msg).parse().unwrap()
+    }
+}
+
+enum Role {
+    Source,
+    Conduit,
+    Sink
+}
+
+struct Field {
+    name: String,
+    role: Option<Role>,
+    ty: Vec<TokenTree>
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut pos = 0;
+
+    // Skip attributes and visibility up to the `struct` keyword.
+    loop {
+        match tokens.get(pos) {
+            None => return Err("`#[derive(Conduit)]` only supports structs".to_string()),
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => break,
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "enum" || ident.to_string() == "union" =>
+                return Err("`#[derive(Conduit)]` only supports structs".to_string()),
+            _ => pos += 1
+        }
+    }
+    pos += 1;
+
+    let name = match tokens.get(pos) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected a struct name".to_string())
+    };
+    pos += 1;
+
+    let mut generics = Vec::new();
+    if is_punct(tokens.get(pos), '<') {
+        let mut depth = 0;
+        loop {
+            let token = match tokens.get(pos) {
+                None => return Err("unterminated generics".to_string()),
+                Some(token) => token.clone()
+            };
+            pos += 1;
+            if is_punct(Some(&token), '<') {
+                depth += 1;
+            } else if is_punct(Some(&token), '>') && !is_arrow(&generics) {
+                depth -= 1;
+            }
+            generics.push(token);
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut where_clause = Vec::new();
+    let body = loop {
+        match tokens.get(pos) {
+            None => return Err("`#[derive(Conduit)]` only supports structs with named fields".to_string()),
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => break group.stream(),
+            Some(token) => where_clause.push(token.clone())
+        }
+        pos += 1;
+    };
+
+    let fields = parse_fields(body)?;
+    let mut source = None;
+    let mut conduits = Vec::new();
+    let mut sink = None;
+    for field in fields {
+        match field.role {
+            None => (),
+            Some(Role::Source) => {
+                if source.is_some() {
+                    return Err("only one field may be marked `#[source]`".to_string());
+                }
+                source = Some(field);
+            },
+            Some(Role::Conduit) => conduits.push(field),
+            Some(Role::Sink) => {
+                if sink.is_some() {
+                    return Err("only one field may be marked `#[sink]`".to_string());
+                }
+                sink = Some(field);
+            }
+        }
+    }
+    let source = source.ok_or_else(|| "missing a field marked `#[source]`".to_string())?;
+    let sink = sink.ok_or_else(|| "missing a field marked `#[sink]`".to_string())?;
+    let result = last_type_argument(&sink.ty)
+        .ok_or_else(|| "the `#[sink]` field must have a type of the form `Sink<.., R>`".to_string())?;
+
+    let (impl_generics, type_generics) = split_generics(&generics);
+    let mut pipeline = format!("self.{}", source.name);
+    for conduit in &conduits {
+        pipeline.push_str(&format!(".fuse(self.{})", conduit.name));
+    }
+
+    let output = format!(
+        "impl {impl_generics} {name} {type_generics} {where_clause} {{
+            /// Runs the pipeline, fusing the source with the conduits in declaration order,
+            /// and connecting the result to the sink.
+            pub fn run(self) -> {result} {{
+                {pipeline}.connect(self.{sink})
+            }}
+        }}",
+        impl_generics = impl_generics,
+        name = name,
+        type_generics = type_generics,
+        where_clause = stream(&where_clause),
+        result = stream(&result),
+        pipeline = pipeline,
+        sink = sink.name
+    );
+    output.parse().map_err(|_| "failed to generate the `run` method".to_string())
+}
+
+fn parse_fields(body: TokenStream) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    for tokens in split_top_level(&body.into_iter().collect::<Vec<_>>()) {
+        let mut role = None;
+        let mut pos = 0;
+        while is_punct(tokens.get(pos), '#') {
+            if let Some(TokenTree::Group(group)) = tokens.get(pos + 1) {
+                let attr = group.stream().into_iter().next().map(|t| t.to_string());
+                let marked = match attr.as_deref() {
+                    Some("source") => Some(Role::Source),
+                    Some("conduit") => Some(Role::Conduit),
+                    Some("sink") => Some(Role::Sink),
+                    _ => None
+                };
+                if marked.is_some() {
+                    if role.is_some() {
+                        return Err("a field can only have one of `#[source]`, `#[conduit]` and `#[sink]`".to_string());
+                    }
+                    role = marked;
+                }
+            }
+            pos += 2;
+        }
+        // Skip the visibility, e.g. `pub` or `pub(crate)`.
+        if let Some(TokenTree::Ident(ident)) = tokens.get(pos) {
+            if ident.to_string() == "pub" {
+                pos += 1;
+                if let Some(TokenTree::Group(group)) = tokens.get(pos) {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        pos += 1;
+                    }
+                }
+            }
+        }
+        let name = match tokens.get(pos) {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            _ => return Err("`#[derive(Conduit)]` only supports structs with named fields".to_string())
+        };
+        if !is_punct(tokens.get(pos + 1), ':') {
+            return Err(format!("expected a type for field `{}`", name));
+        }
+        fields.push(Field { name, role, ty: tokens[pos + 2..].to_vec() });
+    }
+    Ok(fields)
+}
+
+/// Splits the tokens at commas that are not nested inside angle brackets.
+fn split_top_level(tokens: &[TokenTree]) -> Vec<Vec<TokenTree>> {
+    let mut parts = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0;
+    for token in tokens {
+        if is_punct(Some(token), '<') {
+            depth += 1;
+        } else if is_punct(Some(token), '>') && !is_arrow(&current) {
+            depth -= 1;
+        } else if is_punct(Some(token), ',') && depth == 0 {
+            parts.push(current);
+            current = Vec::new();
+            continue;
+        }
+        current.push(token.clone());
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Returns whether the tokens end in the `-` of a `->`.
+fn is_arrow(tokens: &[TokenTree]) -> bool {
+    is_punct(tokens.last(), '-')
+}
+
+/// Returns the last generic argument of a type such as `Sink<'a, I, R>`.
+fn last_type_argument(ty: &[TokenTree]) -> Option<Vec<TokenTree>> {
+    if !is_punct(ty.last(), '>') {
+        return None;
+    }
+    let open = ty.iter().position(|t| is_punct(Some(t), '<'))?;
+    let inner = &ty[open + 1..ty.len() - 1];
+    split_top_level(inner).pop()
+}
+
+/// Splits the generics of the struct into the parameters of the impl,
+/// without defaults, and the arguments of the type, without bounds.
+fn split_generics(generics: &[TokenTree]) -> (String, String) {
+    if generics.is_empty() {
+        return (String::new(), String::new());
+    }
+    let params = split_top_level(&generics[1..generics.len() - 1]);
+    let mut impl_params = Vec::new();
+    let mut type_args = Vec::new();
+    for param in params {
+        let without_default: Vec<TokenTree> = param.iter()
+            .take_while(|t| !is_punct(Some(t), '='))
+            .cloned()
+            .collect();
+        impl_params.push(stream(&without_default));
+        let arg: Vec<TokenTree> = match param.first() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '\'' => param[..2].to_vec(),
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "const" => param[1..2].to_vec(),
+            _ => param[..1].to_vec()
+        };
+        type_args.push(stream(&arg));
+    }
+    (format!("<{}>", impl_params.join(", ")), format!("<{}>", type_args.join(", ")))
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    match token {
+        Some(TokenTree::Punct(p)) => p.as_char() == c,
+        _ => false
+    }
+}
+
+fn stream(tokens: &[TokenTree]) -> String {
+    tokens.iter().cloned().collect::<TokenStream>().to_string()
+}
@@ -0,0 +1,220 @@
+use super::{Chunk, Conduit, ConduitM, Kleisli, leftover_chunk, map_conduit};
+use super::extra::identity;
+
+/// A category, whose arrows can be composed, and which has an identity arrow
+/// for every object.
+///
+/// For conduits, `fuse` is the composition, and `identity` the identity arrow,
+/// so that `c.compose(Category::id())` and `Conduit::id().compose(c)` behave like `c`,
+/// and composition is associative.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Conduit};
+/// use plumbum::algebra::Category;
+///
+/// let double: Conduit<i32, i32> = Conduit::transform(|x| x * 2);
+/// let show: Conduit<i32, String> = Conduit::transform(|x: i32| x.to_string());
+/// let src = Source::from_iter(vec![1, 2]).fuse(show.compose(double));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec!["2", "4"]);
+/// ```
+pub trait Category: Sized {
+    /// The domain of the arrow.
+    type In;
+    /// The codomain of the arrow.
+    type Out;
+    /// The type of arrows from `J` to `P` in the same category.
+    type WithIO<J: 'static, P: 'static>: Category<In = J, Out = P>;
+
+    /// The identity arrow on the domain.
+    fn id() -> Self::WithIO<Self::In, Self::In>;
+
+    /// Composes the arrows, running `other` first and then `self`.
+    fn compose<J: 'static>(self, other: Self::WithIO<J, Self::In>) -> Self::WithIO<J, Self::Out>;
+}
+
+impl<'a, I: 'static, O: 'static> Category for ConduitM<'a, I, O, ()> {
+    type In = I;
+    type Out = O;
+    type WithIO<J: 'static, P: 'static> = ConduitM<'a, J, P, ()>;
+
+    fn id() -> ConduitM<'a, I, I, ()> {
+        identity()
+    }
+
+    fn compose<J: 'static>(self, other: ConduitM<'a, J, I, ()>) -> ConduitM<'a, J, O, ()> {
+        other.fuse(self)
+    }
+}
+
+/// An arrow, i.e. a category into which plain functions can be lifted,
+/// and whose arrows can act on one component of a pair.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Conduit};
+/// use plumbum::algebra::Arrow;
+///
+/// let double: Conduit<i32, i32> = Arrow::arr(|x| x * 2);
+/// let src = Source::from_iter(vec![(1, 'a'), (2, 'b')]).fuse(double.first());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![(2, 'a'), (4, 'b')]);
+/// ```
+pub trait Arrow<'a>: Category {
+    /// Lifts a function into an arrow.
+    fn arr<F>(f: F) -> Self where F: 'a + Fn(Self::In) -> Self::Out;
+
+    /// Runs the arrow on the left component of a pair, passing the right one through.
+    ///
+    /// For conduits, each output is paired with the right component of the input
+    /// consumed last, so outputs produced before the first input are discarded.
+    fn first<C: 'static + Clone>(self) -> First<Self, C>;
+
+    /// Runs the arrow on the right component of a pair, passing the left one through.
+    ///
+    /// Like with `first`, outputs produced before the first input are discarded.
+    fn second<C: 'static + Clone>(self) -> Second<Self, C>;
+}
+
+/// The arrow `Arrow::first` turns `A` into, from `(A::In, C)` to `(A::Out, C)`.
+pub type First<A, C> = <A as Category>::WithIO<(<A as Category>::In, C), (<A as Category>::Out, C)>;
+
+/// The arrow `Arrow::second` turns `A` into, from `(C, A::In)` to `(C, A::Out)`.
+pub type Second<A, C> = <A as Category>::WithIO<(C, <A as Category>::In), (C, <A as Category>::Out)>;
+
+/// For conduits, `first` feeds the conduit one value at a time, and pairs each
+/// value it produces with the right component of the input it last consumed.
+/// Values produced before the first input are discarded, as there is nothing
+/// to pair them with.
+impl<'a, I: 'static, O: 'static> Arrow<'a> for ConduitM<'a, I, O, ()> {
+    fn arr<F>(f: F) -> ConduitM<'a, I, O, ()> where F: 'a + Fn(I) -> O {
+        map_conduit(f)
+    }
+
+    fn first<C: 'static + Clone>(self) -> ConduitM<'a, (I, C), (O, C), ()> {
+        first_with(self, None)
+    }
+
+    fn second<C: 'static + Clone>(self) -> ConduitM<'a, (C, I), (C, O), ()> {
+        let swap_in = Conduit::transform(|(c, i)| (i, c));
+        let swap_out = Conduit::transform(|(o, c)| (c, o));
+        swap_in.fuse(self.first()).fuse(swap_out)
+    }
+}
+
+/// A functor in its last type parameter, so that generic code can map over
+/// the return value of a conduit like over any other container.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::ConduitM;
+/// use plumbum::algebra::Functor;
+///
+/// fn describe<'a, T: Functor<'a, i32>>(t: T) -> T::Mapped<String> {
+///     t.fmap(|x| format!("got {}", x))
+/// }
+///
+/// let c: ConduitM<(), (), i32> = 42.into();
+/// assert_eq!(describe(c), "got 42".to_string().into());
+/// ```
+pub trait Functor<'a, A> {
+    /// The same functor, holding a `B` instead of an `A`.
+    type Mapped<B>;
+
+    /// Applies the function to the value inside the functor.
+    fn fmap<B, F>(self, f: F) -> Self::Mapped<B> where F: 'a + FnOnce(A) -> B;
+}
+
+impl<'a, I, O, A> Functor<'a, A> for ConduitM<'a, I, O, A> {
+    type Mapped<B> = ConduitM<'a, I, O, B>;
+
+    fn fmap<B, F>(self, f: F) -> ConduitM<'a, I, O, B> where F: 'a + FnOnce(A) -> B {
+        self.map(f)
+    }
+}
+
+/// A monad, i.e. a functor whose values can be sequenced,
+/// with each step depending on the result of the previous one.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::ConduitM;
+/// use plumbum::algebra::Monad;
+///
+/// fn double<'a, M: Monad<'a, i32, Mapped<i32> = M>>(m: M) -> M {
+///     m.bind::<i32, _>(|x| M::pure_(x * 2))
+/// }
+///
+/// let c: ConduitM<(), (), i32> = 42.into();
+/// assert_eq!(double(c), 84.into());
+/// ```
+pub trait Monad<'a, A>: Functor<'a, A> {
+    /// Wraps a plain value.
+    fn pure_(a: A) -> Self;
+
+    /// Passes the value to a function returning the next step.
+    fn bind<B, F>(self, f: F) -> Self::Mapped<B> where F: 'a + FnOnce(A) -> Self::Mapped<B>;
+}
+
+impl<'a, I, O, A> Monad<'a, A> for ConduitM<'a, I, O, A> {
+    fn pure_(a: A) -> ConduitM<'a, I, O, A> {
+        a.into()
+    }
+
+    fn bind<B, F>(self, f: F) -> ConduitM<'a, I, O, B> where F: 'a + FnOnce(A) -> ConduitM<'a, I, O, B> {
+        self.and_then(f)
+    }
+}
+
+fn first_with<'a, I: 'static, O: 'static, C: 'static + Clone>(conduit: ConduitM<'a, I, O, ()>, side: Option<C>)
+    -> ConduitM<'a, (I, C), (O, C), ()> {
+    match conduit {
+        ConduitM::Pure(a) => ConduitM::Pure(a),
+        ConduitM::Defer(k) => ConduitM::Defer(Kleisli::from(move |_| first_with(k.run(()), side))),
+        ConduitM::Flush(k) => ConduitM::Flush(Kleisli::from(move |_| first_with(k.run(()), side))),
+        ConduitM::Yield(os, k) => match side {
+            None => ConduitM::Defer(Kleisli::from(move |_| first_with(k.run(()), None))),
+            Some(c) => {
+                let pairs = os.into_iter().map(|o| (o, c.clone())).collect();
+                ConduitM::Yield(pairs, Kleisli::from(move |_| first_with(k.run(()), Some(c))))
+            }
+        },
+        ConduitM::Leftover(is, k) => match side {
+            Some(ref c) if !is.is_empty() => {
+                let pairs = is.into_iter().map(|i| (i, c.clone())).collect();
+                ConduitM::Leftover(pairs, Kleisli::from(move |_| first_with(k.run(()), side)))
+            },
+            _ => ConduitM::Defer(Kleisli::from(move |_| first_with(k.run(()), side)))
+        },
+        ConduitM::Await(k) => ConduitM::Await(Kleisli::from(move |chunk: Chunk<Vec<(I, C)>>| {
+            match chunk {
+                Chunk::Chunk(mut pairs) => if pairs.is_empty() {
+                    first_with(k.run(Chunk::Chunk(Vec::new())), side)
+                } else {
+                    // Feed a single value at a time, so that every output is paired
+                    // with the right component of the input it was produced from.
+                    let rest = pairs.split_off(1);
+                    let (i, c) = pairs.pop().unwrap();
+                    let next = first_with(k.run(Chunk::Chunk(vec![i])), Some(c));
+                    if rest.is_empty() { next } else { leftover_chunk(rest).and(next) }
+                },
+                Chunk::Flush => first_with(k.run(Chunk::Flush), side),
+                Chunk::End => first_with(k.run(Chunk::End), side)
+            }
+        }))
+    }
+}
+
+#[test]
+fn arrow_second_pairs_every_output() {
+    use std::iter::FromIterator;
+    use super::Source;
+    use super::extra::duplicate;
+    let src = Source::from_iter(vec![('a', 1), ('b', 2)]).fuse(duplicate().second());
+    assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![('a', 1), ('a', 1), ('b', 2), ('b', 2)]);
+}
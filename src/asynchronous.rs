@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+use std::future::{Future, IntoFuture, poll_fn};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use tokio::sync::mpsc::{OwnedPermit, Receiver, Sender};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+
+use super::{Source, Sink, Drive, drive_pipeline, consume, defer, no_input, park, produce, take_parked};
+
+thread_local! {
+    /// The waker of the task whose `connect_async` is running a step on this thread.
+    static WAKER: RefCell<Option<Waker>> = const { RefCell::new(None) };
+}
+
+/// A source that is meant to be driven by `connect_async`.
+///
+/// Whenever the sink is waiting for input and the source defers,
+/// or the sink defers by itself, the driver gives control back to the `tokio` scheduler
+/// instead of busily driving the source, so that other tasks can make progress.
+///
+/// Combinators that merge or zip sources, like `merge_n` or `zip_sources_n`, pass
+/// these defers on. Iterating over a source, also through `Peekable` or `into_dyn`,
+/// does not, so an async source must not be wrapped in those: the iterator
+/// would block the task until the source produces a value.
+pub type AsyncSource<'a, O> = Source<'a, O>;
+
+/// A sink that is meant to be driven by `connect_async`.
+pub type AsyncSink<'a, I, A> = Sink<'a, I, A>;
+
+/// Pulls data from the source and pushes it into the sink,
+/// giving control back to the `tokio` scheduler whenever either side defers.
+///
+/// If the side that defers is waiting for a channel, the task sleeps until
+/// the channel wakes it, otherwise it is rescheduled right away.
+pub async fn connect_async<O: 'static, A>(source: AsyncSource<'static, O>, sink: AsyncSink<'static, O, A>) -> A {
+    let mut pipeline = Some((source, sink));
+    poll_fn(move |cx| {
+        let (mut src, mut sink) = pipeline.take().expect("connect_async polled after completion");
+        let outer = WAKER.with(|waker| waker.replace(Some(cx.waker().clone())));
+        take_parked();
+        let poll = loop {
+            match drive_pipeline(src, sink, no_input) {
+                Drive::Done(_, a) => break Poll::Ready(a),
+                Drive::Ready(next_src, next_sink) => {
+                    src = next_src;
+                    sink = next_sink;
+                },
+                Drive::Suspend(next_src, next_sink) => {
+                    if !take_parked() {
+                        cx.waker().wake_by_ref();
+                    }
+                    pipeline = Some((next_src, next_sink));
+                    break Poll::Pending;
+                }
+            }
+        };
+        WAKER.with(|waker| waker.replace(outer));
+        poll
+    }).await
+}
+
+/// Polls a future-like operation with the waker of the task running the current
+/// `connect_async` step. If it is pending, the step that defers next makes the task
+/// sleep until it is woken. Outside of `connect_async`, nothing will wake the task.
+fn poll_async<T, F: FnOnce(&mut Context) -> Poll<T>>(f: F) -> Poll<T> {
+    match WAKER.with(|waker| waker.borrow().clone()) {
+        Some(waker) => {
+            let poll = f(&mut Context::from_waker(&waker));
+            if poll.is_pending() {
+                park();
+            }
+            poll
+        },
+        None => f(&mut Context::from_waker(Waker::noop()))
+    }
+}
+
+/// Awaiting a source drives it to completion with `connect_async`,
+/// and collects the values it produces.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+///
+/// let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let values = rt.block_on(async { Source::from_iter(vec![42, 43]).await });
+/// assert_eq!(values, vec![42, 43]);
+/// ```
+impl<O: 'static> IntoFuture for AsyncSource<'static, O> {
+    type Output = Vec<O>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Vec<O>>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(connect_async(self, Sink::fold(Vec::new(), |mut v, o| {
+            v.push(o);
+            v
+        })))
+    }
+}
+
+/// An async source that produces values it receives from the given `tokio` channel.
+///
+/// While the channel is empty, the source defers, so that `connect_async`
+/// puts the task to sleep until a value arrives. Terminates once all senders are dropped.
+/// See `AsyncSource` for the combinators that must not wrap it.
+pub fn tokio_receiver_source<T: 'static>(mut rx: Receiver<T>) -> AsyncSource<'static, T> {
+    defer().and_then(move |_| {
+        match poll_async(|cx| rx.poll_recv(cx)) {
+            Poll::Ready(Some(x)) => produce(x).and(tokio_receiver_source(rx)),
+            Poll::Ready(None) => ().into(),
+            Poll::Pending => tokio_receiver_source(rx)
+        }
+    })
+}
+
+/// An async sink that consumes values and sends them to the given `tokio` channel.
+///
+/// While the channel is full, the sink defers, so that `connect_async`
+/// puts the task to sleep until there is room. If the receiver is dropped,
+/// returns the value that could not be sent.
+pub fn tokio_sender_sink<T: 'static>(tx: Sender<T>) -> AsyncSink<'static, T, Result<(), SendError<T>>> {
+    consume().and_then(|xo| {
+        match xo {
+            None => Ok(()).into(),
+            Some(x) => tokio_send(tx, x)
+        }
+    })
+}
+
+type Reserve<T> = Pin<Box<dyn Future<Output = Result<OwnedPermit<T>, SendError<()>>>>>;
+
+fn tokio_send<T: 'static>(tx: Sender<T>, x: T) -> AsyncSink<'static, T, Result<(), SendError<T>>> {
+    match tx.try_send(x) {
+        Ok(_) => tokio_sender_sink(tx),
+        Err(TrySendError::Full(x)) => tokio_reserve(Box::pin(tx.reserve_owned()), x),
+        Err(TrySendError::Closed(x)) => Err(SendError(x)).into()
+    }
+}
+
+fn tokio_reserve<T: 'static>(mut reserve: Reserve<T>, x: T) -> AsyncSink<'static, T, Result<(), SendError<T>>> {
+    defer().and_then(move |_| {
+        match poll_async(|cx| reserve.as_mut().poll(cx)) {
+            Poll::Ready(Ok(permit)) => tokio_sender_sink(permit.send(x)),
+            Poll::Ready(Err(_)) => Err(SendError(x)).into(),
+            Poll::Pending => tokio_reserve(reserve, x)
+        }
+    })
+}
+
+#[test]
+fn connect_async_fold() {
+    use std::iter::FromIterator;
+    use tokio::runtime::Builder;
+    use super::defer;
+
+    let src = defer().and(Source::from_iter(vec![42, 43])).and(defer());
+    let sink = Sink::fold(0, |x, y| x + y);
+    let rt = Builder::new_current_thread().build().unwrap();
+    assert_eq!(rt.block_on(connect_async(src, sink)), 85);
+}
+
+#[test]
+fn await_source() {
+    use std::iter::FromIterator;
+    use tokio::runtime::Builder;
+
+    let src = defer().and(Source::from_iter(vec![42, 43])).and(defer());
+    let rt = Builder::new_current_thread().build().unwrap();
+    assert_eq!(rt.block_on(async { src.await }), vec![42, 43]);
+}
+
+#[test]
+fn connect_async_tokio_channels() {
+    use std::iter::FromIterator;
+    use tokio::runtime::Builder;
+    use tokio::sync::mpsc::channel;
+
+    let rt = Builder::new_current_thread().build().unwrap();
+    let (tx, rx) = channel(4);
+    let res = rt.block_on(connect_async(Source::from_iter(vec![42, 43]), tokio_sender_sink(tx)));
+    assert!(res.is_ok());
+    let sum = rt.block_on(connect_async(tokio_receiver_source(rx), Sink::fold(0, |x, y| x + y)));
+    assert_eq!(sum, 85);
+}
+
+#[test]
+fn connect_async_sleeps_on_empty_channel() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+    use tokio::sync::mpsc::channel;
+
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    let (tx, rx) = channel(4);
+    let mut pipeline = Box::pin(connect_async(tokio_receiver_source(rx), Sink::fold(0, |x, y| x + y)));
+    while pipeline.as_mut().poll(&mut cx).is_pending() {
+        if wakes.0.load(Ordering::SeqCst) == 0 {
+            break;
+        }
+        wakes.0.store(0, Ordering::SeqCst);
+    }
+    assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+    tx.try_send(42).unwrap();
+    drop(tx);
+    let mut sum = pipeline.as_mut().poll(&mut cx);
+    while sum.is_pending() {
+        sum = pipeline.as_mut().poll(&mut cx);
+    }
+    assert_eq!(sum, Poll::Ready(42));
+}
+
+#[test]
+fn connect_async_merges_channels() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+    use tokio::sync::mpsc::channel;
+    use super::extra::merge_n;
+
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    // Polls until the pipeline either completes, or sleeps without asking to be woken.
+    let mut poll_until_parked = |pipeline: &mut Pin<Box<dyn Future<Output = i32>>>| {
+        loop {
+            wakes.0.store(0, Ordering::SeqCst);
+            let poll = pipeline.as_mut().poll(&mut cx);
+            if poll.is_ready() || wakes.0.load(Ordering::SeqCst) == 0 {
+                return poll;
+            }
+        }
+    };
+
+    let (tx1, rx1) = channel(4);
+    let (tx2, rx2) = channel(4);
+    let src = merge_n(vec![tokio_receiver_source(rx1), tokio_receiver_source(rx2)]);
+    let mut pipeline: Pin<Box<dyn Future<Output = i32>>> = Box::pin(connect_async(src, Sink::fold(0, |x, y| x + y)));
+    assert_eq!(poll_until_parked(&mut pipeline), Poll::Pending);
+    tx2.try_send(2).unwrap();
+    assert_eq!(poll_until_parked(&mut pipeline), Poll::Pending);
+    tx1.try_send(1).unwrap();
+    drop(tx1);
+    assert_eq!(poll_until_parked(&mut pipeline), Poll::Pending);
+    drop(tx2);
+    assert_eq!(poll_until_parked(&mut pipeline), Poll::Ready(3));
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+use super::{Chunk, ConduitM};
+
+/// Runs a conduit step by step, feeding it one input value at a time.
+///
+/// This makes it possible to test a conduit in isolation, without building a
+/// source and a sink around it. Leftovers are handed back to the conduit the
+/// next time it awaits, and requests to defer or flush are ignored.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::Driver;
+/// use plumbum::extra::{chunk, count};
+///
+/// let mut driver = Driver::new(chunk::<i32>(2));
+/// assert!(driver.feed(1).is_empty());
+/// assert_eq!(driver.feed(2), vec![vec![1, 2]]);
+/// assert!(driver.feed(3).is_empty());
+/// assert_eq!(driver.finish(), (vec![vec![3]], Some(())));
+///
+/// let mut driver = Driver::new(count::<i32>());
+/// driver.feed(1);
+/// assert_eq!(driver.finish().1, Some(1));
+/// ```
+pub struct Driver<'a, I, O, A> {
+    state: Option<ConduitM<'a, I, O, A>>
+}
+
+impl<'a, I: 'static, O: 'static, A> Driver<'a, I, O, A> {
+
+    /// Creates a driver for the conduit. The conduit does not run before it is fed.
+    pub fn new(conduit: ConduitM<'a, I, O, A>) -> Driver<'a, I, O, A> {
+        Driver { state: Some(conduit) }
+    }
+
+    /// Sends a single value to the conduit, as if it was produced upstream,
+    /// and runs the conduit until it awaits more input or terminates.
+    ///
+    /// Returns the values the conduit produced in the meantime.
+    /// Once the conduit terminated, the input is discarded.
+    pub fn feed(&mut self, input: I) -> Vec<O> {
+        self.run(Some(Chunk::Chunk(vec![input])))
+    }
+
+    /// Signals the end of the input to the conduit, and runs it to completion.
+    ///
+    /// Returns the values the conduit produced in the meantime, and its result.
+    /// The result is `None` if it was already returned by an earlier call.
+    pub fn finish(&mut self) -> (Vec<O>, Option<A>) {
+        let output = self.run(None);
+        match self.state.take() {
+            Some(ConduitM::Pure(a)) => (output, Some(*a)),
+            _ => (output, None)
+        }
+    }
+
+    /// Runs the conduit, answering its first await with the given chunk, if any,
+    /// and every await with the end of the input otherwise.
+    fn run(&mut self, input: Option<Chunk<Vec<I>>>) -> Vec<O> {
+        let at_end = input.is_none();
+        let mut pending: VecDeque<_> = input.into_iter().collect();
+        let mut output = Vec::new();
+        let mut state = match self.state.take() {
+            None => return output,
+            Some(state) => state
+        };
+        loop {
+            state = match state {
+                ConduitM::Pure(a) => {
+                    self.state = Some(ConduitM::Pure(a));
+                    return output;
+                },
+                ConduitM::Defer(k) => k.run(()),
+                ConduitM::Flush(k) => k.run(()),
+                ConduitM::Await(k) => match pending.pop_front() {
+                    Some(chunk) => k.run(chunk),
+                    None if at_end => k.run(Chunk::End),
+                    None => {
+                        self.state = Some(ConduitM::Await(k));
+                        return output;
+                    }
+                },
+                ConduitM::Yield(o, k) => {
+                    output.extend(o);
+                    k.run(())
+                },
+                ConduitM::Leftover(i, k) => {
+                    pending.push_front(Chunk::Chunk(i));
+                    k.run(())
+                }
+            };
+        }
+    }
+}
+
+#[test]
+fn driver_returns_leftovers_to_the_conduit() {
+    use super::{consume, leftover, produce};
+
+    let conduit = consume().and_then(|io: Option<i32>| {
+        leftover(io.unwrap() + 1).and(consume()).and_then(|io| produce(io.unwrap()))
+    });
+    let mut driver = Driver::new(conduit);
+    assert_eq!(driver.feed(1), vec![2]);
+    assert_eq!(driver.feed(5), vec![]);
+    assert_eq!(driver.finish(), (vec![], Some(())));
+    assert_eq!(driver.finish(), (vec![], None));
+}
+
+#[test]
+fn driver_keeps_signalling_end() {
+    use super::consume;
+
+    let sink = consume::<i32, ()>().and(consume()).and(consume());
+    let mut driver = Driver::new(sink);
+    assert_eq!(driver.finish(), (vec![], Some(None)));
+}
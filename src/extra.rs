@@ -0,0 +1,1758 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::mem::{self, replace};
+use std::iter::FromIterator;
+use std::ops::{Add, Sub};
+
+use super::{Chunk, Conduit, ConduitM, Sink, Source, SourceIter, consume, defer, leftover, park, produce, produce_chunk};
+use super::iter::Step;
+use super::mem::charge;
+#[cfg(all(feature = "metrics", feature = "metrics-per-chunk"))]
+use super::consume_chunk;
+
+/// A sink that counts the number of values it consumes.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::count;
+///
+/// let src: Source<char> = produce('a').and(produce('b'));
+/// assert_eq!(src.connect(count()), 2);
+/// ```
+pub fn count<'a, I: 'a>() -> Sink<'a, I, usize> {
+    Sink::fold(0, |n, _| n + 1)
+}
+
+/// A sink that returns `true` as soon as it consumes a value
+/// satisfying the predicate, and `false` if there is none.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::any;
+///
+/// let src = Source::from_iter(vec![1, 3, 4, 5]);
+/// assert_eq!(src.connect(any(|x| x % 2 == 0)), true);
+/// ```
+pub fn any<'a, I: 'a, F>(mut pred: F) -> Sink<'a, I, bool>
+    where F: 'a + FnMut(&I) -> bool {
+    Sink::sink(false, move |_, i| if pred(&i) { Err(true) } else { Ok(false) })
+}
+
+/// A sink that returns `false` as soon as it consumes a value
+/// not satisfying the predicate, and `true` if there is none.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::all;
+///
+/// let src: Source<i32> = produce(2).and(produce(3));
+/// assert_eq!(src.connect(all(|x| x % 2 == 0)), false);
+/// ```
+pub fn all<'a, I: 'a, F>(mut pred: F) -> Sink<'a, I, bool>
+    where F: 'a + FnMut(&I) -> bool {
+    Sink::sink(true, move |_, i| if pred(&i) { Ok(true) } else { Err(false) })
+}
+
+/// A sink that returns the first value it consumes which satisfies the predicate.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::find;
+///
+/// let src: Source<i32> = produce(1).and(produce(2)).and(produce(4));
+/// assert_eq!(src.connect(find(|x| x % 2 == 0)), Some(2));
+/// ```
+pub fn find<'a, I: 'a, F>(mut pred: F) -> Sink<'a, I, Option<I>>
+    where F: 'a + FnMut(&I) -> bool {
+    Sink::sink(None, move |_, i| if pred(&i) { Err(Some(i)) } else { Ok(None) })
+}
+
+/// A sink that returns the first value it consumes, if any.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::first;
+///
+/// let src: Source<i32> = produce(1).and(produce(2));
+/// assert_eq!(src.connect(first()), Some(1));
+/// ```
+pub fn first<'a, I: 'a>() -> Sink<'a, I, Option<I>> {
+    consume()
+}
+
+/// A sink that returns the last value it consumes, if any.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::last;
+///
+/// let src: Source<i32> = produce(1).and(produce(2));
+/// assert_eq!(src.connect(last()), Some(2));
+/// ```
+pub fn last<'a, I: 'a>() -> Sink<'a, I, Option<I>> {
+    Sink::fold(None, |_, i| Some(i))
+}
+
+/// A sink that adds up the values it consumes, starting from `I::default()`.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::sum;
+///
+/// let src: Source<i32> = produce(1).and(produce(2));
+/// assert_eq!(src.connect(sum()), 3);
+/// ```
+pub fn sum<'a, I: 'a + Default + Add<Output = I>>() -> Sink<'a, I, I> {
+    Sink::fold(I::default(), |a, i| a + i)
+}
+
+/// A sink that folds the values it consumes into an accumulator.
+///
+/// Unlike `Sink::fold`, the function may carry mutable state.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::fold;
+///
+/// let src: Source<i32> = produce(1).and(produce(2));
+/// assert_eq!(src.connect(fold(10, |a, i| a * i)), 20);
+/// ```
+pub fn fold<'a, I: 'a, A: 'a, F>(init: A, mut f: F) -> Sink<'a, I, A>
+    where F: 'a + FnMut(A, I) -> A {
+    Sink::sink(init, move |a, i| Ok(f(a, i)))
+}
+
+/// A sink that collects the values it consumes into a vector.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::to_vec;
+///
+/// let src: Source<i32> = produce(1).and(produce(2));
+/// assert_eq!(src.connect(to_vec()), vec![1, 2]);
+/// ```
+pub fn to_vec<'a, I: 'a>() -> Sink<'a, I, Vec<I>> {
+    Sink::fold(Vec::new(), |mut v, i| {
+        charge::<I>(1);
+        v.push(i);
+        v
+    })
+}
+
+/// A source that produces the values of the iterator.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::extra::{from_iter, to_vec};
+///
+/// assert_eq!(from_iter(1..4).connect(to_vec()), vec![1, 2, 3]);
+/// ```
+pub fn from_iter<'a, T: 'a, It>(iter: It) -> Source<'a, T>
+    where It: IntoIterator<Item = T>, It::IntoIter: 'a {
+    Source::from_iter(iter)
+}
+
+/// A source that produces the values of the vector.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::extra::{from_vec, to_vec};
+///
+/// assert_eq!(from_vec(vec![1, 2]).connect(to_vec()), vec![1, 2]);
+/// ```
+pub fn from_vec<'a, T: 'a>(v: Vec<T>) -> Source<'a, T> {
+    v.into()
+}
+
+/// A conduit that forwards the first `n` values, and then terminates.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{take, to_vec};
+///
+/// let src: Source<i32> = produce(1).and(produce(2)).and(produce(3));
+/// assert_eq!(src.fuse(take(2)).connect(to_vec()), vec![1, 2]);
+/// ```
+pub fn take<'a, T: 'static>(n: usize) -> Conduit<'a, T, T> {
+    if n == 0 {
+        return ().into();
+    }
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => produce(t).and(take(n - 1))
+        }
+    })
+}
+
+/// A conduit that discards the first `n` values, and forwards the rest.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{skip, to_vec};
+///
+/// let src: Source<i32> = produce(1).and(produce(2)).and(produce(3));
+/// assert_eq!(src.fuse(skip(2)).connect(to_vec()), vec![3]);
+/// ```
+pub fn skip<'a, T: 'static>(n: usize) -> Conduit<'a, T, T> {
+    if n == 0 {
+        return identity();
+    }
+    consume().and_then(move |io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(_) => skip(n - 1)
+        }
+    })
+}
+
+/// An alias of `skip`.
+///
+/// Note that it shadows the prelude's `drop` wherever `plumbum::extra::*` is imported,
+/// import `skip` instead in that case.
+#[inline]
+pub fn drop<'a, T: 'static>(n: usize) -> Conduit<'a, T, T> {
+    skip(n)
+}
+
+/// A conduit that forwards the values satisfying the predicate.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{filter, to_vec};
+///
+/// let src: Source<i32> = produce(1).and(produce(2)).and(produce(3));
+/// assert_eq!(src.fuse(filter(|x| x % 2 == 1)).connect(to_vec()), vec![1, 3]);
+/// ```
+pub fn filter<'a, T: 'static, F>(mut pred: F) -> Conduit<'a, T, T>
+    where F: 'a + FnMut(&T) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => if pred(&t) {
+                produce(t).and(filter(pred))
+            } else {
+                filter(pred)
+            }
+        }
+    })
+}
+
+/// A conduit that forwards `o` for each value for which `f` returns `Some(o)`.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{filter_map, to_vec};
+///
+/// let src: Source<&str> = produce("1").and(produce("x")).and(produce("3"));
+/// assert_eq!(src.fuse(filter_map(|s: &str| s.parse::<i32>().ok())).connect(to_vec()), vec![1, 3]);
+/// ```
+pub fn filter_map<'a, I: 'static, O: 'static, F>(mut f: F) -> Conduit<'a, I, O>
+    where F: 'a + FnMut(I) -> Option<O> {
+    consume().and_then(move |io| {
+        match io.map(&mut f) {
+            None => ().into(),
+            Some(None) => filter_map(f),
+            Some(Some(o)) => produce(o).and(filter_map(f))
+        }
+    })
+}
+
+/// A conduit that pairs each value with its zero-based index.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{enumerate, to_vec};
+///
+/// let src: Source<char> = produce('a').and(produce('b'));
+/// assert_eq!(src.fuse(enumerate()).connect(to_vec()), vec![(0, 'a'), (1, 'b')]);
+/// ```
+pub fn enumerate<'a, T: 'static>() -> Conduit<'a, T, (usize, T)> {
+    map_accum_l(0, |n, t| (n + 1, (n, t)))
+}
+
+/// A conduit that drops values equal to the value before them.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{dedup, to_vec};
+///
+/// let src: Source<i32> = produce(1).and(produce(1)).and(produce(2)).and(produce(1));
+/// assert_eq!(src.fuse(dedup()).connect(to_vec()), vec![1, 2, 1]);
+/// ```
+pub fn dedup<'a, T: 'static + PartialEq + Clone>() -> Conduit<'a, T, T> {
+    consume().and_then(|io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => produce(t.clone()).and(dedup_from(t))
+        }
+    })
+}
+
+fn dedup_from<'a, T: 'static + PartialEq + Clone>(prev: T) -> Conduit<'a, T, T> {
+    consume().and_then(move |io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => if t == prev {
+                dedup_from(prev)
+            } else {
+                produce(t.clone()).and(dedup_from(t))
+            }
+        }
+    })
+}
+
+/// A conduit that drops values that it has forwarded before.
+///
+/// Every distinct value is kept in memory.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{unique, to_vec};
+///
+/// let src: Source<i32> = produce(1).and(produce(2)).and(produce(1));
+/// assert_eq!(src.fuse(unique()).connect(to_vec()), vec![1, 2]);
+/// ```
+pub fn unique<'a, T: 'static + Eq + Hash + Clone>() -> Conduit<'a, T, T> {
+    unique_from(HashSet::new())
+}
+
+fn unique_from<'a, T: 'static + Eq + Hash + Clone>(mut seen: HashSet<T>) -> Conduit<'a, T, T> {
+    consume().and_then(move |io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => if seen.contains(&t) {
+                unique_from(seen)
+            } else {
+                charge::<T>(1);
+                seen.insert(t.clone());
+                produce(t).and(unique_from(seen))
+            }
+        }
+    })
+}
+
+/// A conduit that forwards all values, with `sep` in between each two of them.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Source, produce};
+/// use plumbum::extra::{intersperse, to_vec};
+///
+/// let src: Source<i32> = produce(1).and(produce(2)).and(produce(3));
+/// assert_eq!(src.fuse(intersperse(0)).connect(to_vec()), vec![1, 0, 2, 0, 3]);
+/// ```
+pub fn intersperse<'a, T: 'static + Clone>(sep: T) -> Conduit<'a, T, T> {
+    consume().and_then(|io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => produce(t).and(intersperse_from(sep))
+        }
+    })
+}
+
+fn intersperse_from<'a, T: 'static + Clone>(sep: T) -> Conduit<'a, T, T> {
+    consume().and_then(move |io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => produce_chunk(vec![sep.clone(), t]).and(intersperse_from(sep))
+        }
+    })
+}
+
+/// A conduit that threads an accumulating state through the stream,
+/// producing one output value for each input value.
+///
+/// The final state is discarded once upstream is exhausted.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink};
+/// use plumbum::extra::map_accum_l;
+///
+/// let src = Source::from_iter(vec![1, 2, 3, 4]);
+/// let sums = map_accum_l(0, |acc, x| (acc + x, acc + x));
+/// let sink = Sink::fold(Vec::new(), |mut v, x| { v.push(x); v });
+/// assert_eq!(src.fuse(sums).connect(sink), vec![1, 3, 6, 10]);
+/// ```
+pub fn map_accum_l<'a, I: 'a, O: 'a, S: 'a, F>(init: S, mut f: F) -> Conduit<'a, I, O>
+    where F: 'a + FnMut(S, I) -> (S, O) {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(i) => {
+                let (s, o) = f(init, i);
+                produce(o).and_then(move |_| map_accum_l(s, f))
+            }
+        }
+    })
+}
+
+/// A pass-through conduit that increments the `metrics` counter `name`
+/// for each element it forwards.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::extra::{from_vec, metered, to_vec};
+///
+/// let src = from_vec(vec![1, 2, 3]).fuse(metered("values"));
+/// assert_eq!(src.connect(to_vec()), vec![1, 2, 3]);
+/// ```
+#[cfg(all(feature = "metrics", not(feature = "metrics-per-chunk")))]
+pub fn metered<'a, T: 'static>(name: &'static str) -> Conduit<'a, T, T> {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => {
+                ::metrics::counter!(name).increment(1);
+                produce(t).and(metered(name))
+            }
+        }
+    })
+}
+
+/// A pass-through conduit that increments the `metrics` counter `name`
+/// by the number of elements it forwards.
+///
+/// With the `metrics-per-chunk` feature enabled, elements are forwarded
+/// and counted a whole chunk at a time instead of individually,
+/// which avoids the per-element overhead on hot paths.
+/// Flushes are passed on downstream once, as by any other conduit.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{Chunk, ConduitM, Kleisli, Sink, Source, consume_chunk, produce_chunk};
+/// use plumbum::extra::metered;
+///
+/// // Records every chunk, and `None` for every flush.
+/// fn record(mut seen: Vec<Option<Vec<i32>>>) -> Sink<'static, i32, Vec<Option<Vec<i32>>>> {
+///     consume_chunk().and_then(move |chunk| match chunk {
+///         Chunk::End => seen.into(),
+///         Chunk::Flush => { seen.push(None); record(seen) },
+///         Chunk::Chunk(v) => { seen.push(Some(v)); record(seen) }
+///     })
+/// }
+///
+/// let src: Source<i32> = produce_chunk(vec![1, 2])
+///     .and(ConduitM::Flush(Kleisli::new()))
+///     .and(produce_chunk(vec![3]));
+/// assert_eq!(src.fuse(metered("values")).connect(record(Vec::new())),
+///            vec![Some(vec![1, 2]), None, Some(vec![3])]);
+/// ```
+#[cfg(all(feature = "metrics", feature = "metrics-per-chunk"))]
+pub fn metered<'a, T: 'static>(name: &'static str) -> Conduit<'a, T, T> {
+    consume_chunk().and_then(move |chunk| {
+        match chunk {
+            Chunk::End => ().into(),
+            Chunk::Flush => metered(name),
+            Chunk::Chunk(v) => {
+                ::metrics::counter!(name).increment(v.len() as u64);
+                produce_chunk(v).and(metered(name))
+            }
+        }
+    })
+}
+
+/// A source that merges the given sources fairly,
+/// pulling one value from each of them in turn.
+///
+/// Exhausted sources are skipped, and the merged source
+/// terminates once all of them are exhausted.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::merge_n;
+///
+/// let src = merge_n(vec![
+///     Source::from_iter(vec![1, 4]),
+///     Source::from_iter(vec![2]),
+///     Source::from_iter(vec![3, 5, 6])
+/// ]);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+/// ```
+pub fn merge_n<'a, T: 'static>(sources: Vec<Source<'a, T>>) -> Source<'a, T> {
+    merge_iters(sources.into_iter().map(SourceIter::new).collect())
+}
+
+fn merge_iters<'a, T: 'static>(mut iters: VecDeque<SourceIter<'a, T>>) -> Source<'a, T> {
+    defer().and_then(move |_| {
+        // Poll every source at most once, and only defer if all of them deferred,
+        // so that a source waiting for input does not hold up the others.
+        let mut parked = true;
+        for _ in 0..iters.len() {
+            let mut iter = iters.pop_front().unwrap();
+            match iter.step() {
+                Step::Ready(t) => {
+                    iters.push_back(iter);
+                    return produce(t).and(merge_iters(iters));
+                },
+                Step::Pending(p) => {
+                    parked &= p;
+                    iters.push_back(iter);
+                },
+                Step::Done => ()
+            }
+        }
+        if iters.is_empty() {
+            return ().into();
+        }
+        if parked {
+            park();
+        }
+        merge_iters(iters)
+    })
+}
+
+/// A source that polls the given sources in turn, producing the first value
+/// that becomes available, and then continuing with the next source.
+/// Exhausted sources are dropped from the rotation.
+///
+/// This is not a concurrent select: each poll runs a source until it produces
+/// a value or terminates, so a slow source delays all others, and finding
+/// each value costs up to one poll per remaining source.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::select_first;
+///
+/// let src = select_first(vec![Source::from_iter(vec![]), Source::from_iter(vec![1, 3]), Source::from_iter(vec![2])]);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+/// ```
+pub fn select_first<'a, T: 'static>(sources: Vec<Source<'a, T>>) -> Source<'a, T> {
+    merge_n(sources)
+}
+
+/// A source that zips the given sources, producing one value from each of them
+/// at a time. Terminates as soon as any source is exhausted,
+/// or immediately if there are no sources.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::zip_sources_n;
+///
+/// let src = zip_sources_n(vec![
+///     Source::from_iter(vec![1, 2]),
+///     Source::from_iter(vec![3, 4, 5]),
+///     Source::from_iter(vec![6, 7])
+/// ]);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![vec![1, 3, 6], vec![2, 4, 7]]);
+/// ```
+pub fn zip_sources_n<'a, T: 'static>(sources: Vec<Source<'a, T>>) -> Source<'a, Vec<T>> {
+    let len = sources.len();
+    zip_iters(sources.into_iter().map(SourceIter::new).collect(), Vec::with_capacity(len))
+}
+
+fn zip_iters<'a, T: 'static>(mut iters: Vec<SourceIter<'a, T>>, mut row: Vec<T>) -> Source<'a, Vec<T>> {
+    defer().and_then(move |_| {
+        if iters.is_empty() {
+            return ().into();
+        }
+        while row.len() < iters.len() {
+            match iters[row.len()].step() {
+                Step::Ready(t) => row.push(t),
+                Step::Pending(parked) => {
+                    if parked {
+                        park();
+                    }
+                    return zip_iters(iters, row);
+                },
+                Step::Done => return ().into()
+            }
+        }
+        let len = iters.len();
+        produce(row).and(zip_iters(iters, Vec::with_capacity(len)))
+    })
+}
+
+/// A sink that distributes the values it consumes across the given sinks,
+/// sending each value to the next sink in turn.
+///
+/// Sinks that terminate early are removed from the rotation.
+/// Once upstream is exhausted, or all sinks have terminated,
+/// returns the results of all sinks in their original order.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink};
+/// use plumbum::extra::round_robin_sink;
+///
+/// let sum = || Sink::fold(0, |x, y| x + y);
+/// let sink = round_robin_sink(vec![sum(), sum()]);
+/// assert_eq!(Source::from_iter(vec![1, 2, 3, 4, 5]).connect(sink), vec![9, 6]);
+/// ```
+pub fn round_robin_sink<'a, T: 'static, A: 'a>(sinks: Vec<Sink<'a, T, A>>) -> Sink<'a, T, Vec<A>> {
+    let mut results = Vec::with_capacity(sinks.len());
+    let mut running = VecDeque::with_capacity(sinks.len());
+    for (idx, sink) in sinks.into_iter().enumerate() {
+        results.push(None);
+        match settle(sink, Vec::new()) {
+            ConduitM::Pure(a) => results[idx] = Some(*a),
+            sink => running.push_back((idx, sink))
+        }
+    }
+    round_robin(running, results)
+}
+
+fn round_robin<'a, T: 'static, A: 'a>(mut running: VecDeque<(usize, Sink<'a, T, A>)>, mut results: Vec<Option<A>>)
+    -> Sink<'a, T, Vec<A>> {
+    if running.is_empty() {
+        return results.into_iter().map(Option::unwrap).collect::<Vec<_>>().into();
+    }
+    consume().and_then(move |io| {
+        match io {
+            None => {
+                for (idx, sink) in running {
+                    results[idx] = Some(finish(sink));
+                }
+                round_robin(VecDeque::new(), results)
+            },
+            Some(t) => {
+                let (idx, sink) = running.pop_front().unwrap();
+                match settle(sink, vec![t]) {
+                    ConduitM::Pure(a) => results[idx] = Some(*a),
+                    sink => running.push_back((idx, sink))
+                }
+                round_robin(running, results)
+            }
+        }
+    })
+}
+
+/// Runs the sink on the pending values until it either returns,
+/// or is waiting for more input.
+fn settle<'a, T: 'static, A>(mut sink: Sink<'a, T, A>, mut pending: Vec<T>) -> Sink<'a, T, A> {
+    loop {
+        sink = match sink {
+            ConduitM::Pure(a) => return ConduitM::Pure(a),
+            ConduitM::Await(k) => if pending.is_empty() {
+                return ConduitM::Await(k);
+            } else {
+                k.run(Chunk::Chunk(mem::take(&mut pending)))
+            },
+            ConduitM::Leftover(mut v, k) => {
+                v.append(&mut pending);
+                pending = v;
+                k.run(())
+            },
+            ConduitM::Defer(k) => k.run(()),
+            ConduitM::Flush(k) => k.run(()),
+            ConduitM::Yield(_, k) => k.run(())
+        }
+    }
+}
+
+/// Signals the end of input to the sink until it returns.
+fn finish<'a, T: 'static, A>(mut sink: Sink<'a, T, A>) -> A {
+    loop {
+        sink = match settle(sink, Vec::new()) {
+            ConduitM::Pure(a) => return *a,
+            ConduitM::Await(k) => k.run(Chunk::End),
+            _ => unreachable!()
+        }
+    }
+}
+
+/// A source that merges two sources, alternately pulling `left_weight`
+/// values from `left` and then `right_weight` values from `right`.
+///
+/// Once either source is exhausted, the other one is drained.
+///
+/// # Panics
+///
+/// Panics if both weights are zero.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::weighted_merge;
+///
+/// let left = Source::from_iter(vec![1, 2, 3, 4, 5]);
+/// let right = Source::from_iter(vec![10, 20]);
+/// let src = weighted_merge(left, right, 2, 1);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 10, 3, 4, 20, 5]);
+/// ```
+pub fn weighted_merge<'a, T: 'static>(left: Source<'a, T>, right: Source<'a, T>,
+                                      left_weight: usize, right_weight: usize) -> Source<'a, T> {
+    assert!(left_weight > 0 || right_weight > 0, "weighted_merge: both weights are zero");
+    weighted(SourceIter::new(left), left_weight, Some((SourceIter::new(right), right_weight)), left_weight)
+}
+
+fn weighted<'a, T: 'static>(mut iter: SourceIter<'a, T>, weight: usize,
+                            other: Option<(SourceIter<'a, T>, usize)>, remaining: usize) -> Source<'a, T> {
+    defer().and_then(move |_| {
+        match other {
+            Some((other_iter, other_weight)) if remaining == 0 => {
+                weighted(other_iter, other_weight, Some((iter, weight)), other_weight)
+            },
+            other => match iter.step() {
+                Step::Ready(t) => produce(t).and(weighted(iter, weight, other, remaining.saturating_sub(1))),
+                Step::Pending(parked) => {
+                    if parked {
+                        park();
+                    }
+                    weighted(iter, weight, other, remaining)
+                },
+                Step::Done => match other {
+                    None => ().into(),
+                    Some((other_iter, other_weight)) => weighted(other_iter, other_weight, None, other_weight)
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that forwards values only while the signal is raised.
+///
+/// The signal is checked once for each value, and values arriving
+/// while it returns `false` are discarded. Reading the signal from an
+/// `Arc<AtomicBool>` allows it to be toggled from another thread.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use plumbum::Source;
+/// use plumbum::extra::gate;
+///
+/// let open = Arc::new(AtomicBool::new(false));
+/// let flag = open.clone();
+/// let src = Source::from_iter(vec![1, 2, 3]).fuse(gate(move || flag.load(Ordering::SeqCst)));
+/// assert_eq!(src.count(), 0);
+/// ```
+pub fn gate<'a, T: 'static, F>(mut signal: F) -> Conduit<'a, T, T>
+    where F: 'a + FnMut() -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => if signal() {
+                produce(t).and(gate(signal))
+            } else {
+                gate(signal)
+            }
+        }
+    })
+}
+
+/// A conduit that forwards each value twice.
+pub fn duplicate<'a, T: 'static + Clone>() -> Conduit<'a, T, T> {
+    replicate(2)
+}
+
+/// A conduit that forwards each value `n` times.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::replicate;
+///
+/// let src = Source::from_iter(vec![1, 2]).fuse(replicate(3));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 1, 1, 2, 2, 2]);
+/// ```
+pub fn replicate<'a, T: 'static + Clone>(n: usize) -> Conduit<'a, T, T> {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => produce_chunk(vec![t; n]).and(replicate(n))
+        }
+    })
+}
+
+/// A conduit that groups consecutive values into chunks,
+/// starting a new chunk whenever `pred(previous, current)` returns `true`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::split_when;
+///
+/// // Split into ascending runs.
+/// let src = Source::from_iter(vec![1, 2, 5, 3, 4, 1]).fuse(split_when(|prev, cur| cur < prev));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![vec![1, 2, 5], vec![3, 4], vec![1]]);
+/// ```
+pub fn split_when<'a, T: 'static, F>(pred: F) -> Conduit<'a, T, Vec<T>>
+    where F: 'a + FnMut(&T, &T) -> bool {
+    split_when_from(Vec::new(), pred)
+}
+
+fn split_when_from<'a, T: 'static, F>(mut acc: Vec<T>, mut pred: F) -> Conduit<'a, T, Vec<T>>
+    where F: 'a + FnMut(&T, &T) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => if acc.is_empty() {
+                ().into()
+            } else {
+                produce(acc)
+            },
+            Some(t) => {
+                let split = match acc.last() {
+                    None => false,
+                    Some(prev) => pred(prev, &t)
+                };
+                if split {
+                    let chunk = replace(&mut acc, vec![t]);
+                    produce(chunk).and(split_when_from(acc, pred))
+                } else {
+                    charge::<T>(1);
+                    acc.push(t);
+                    split_when_from(acc, pred)
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that forwards values up to and including the first one
+/// satisfying the predicate, and then terminates.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::take_until;
+///
+/// let src = Source::from_iter(vec![1, 2, 0, 3]).fuse(take_until(|x| *x == 0));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 0]);
+/// ```
+pub fn take_until<'a, T: 'static, F>(mut pred: F) -> Conduit<'a, T, T>
+    where F: 'a + FnMut(&T) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => if pred(&t) {
+                produce(t)
+            } else {
+                produce(t).and(take_until(pred))
+            }
+        }
+    })
+}
+
+/// A conduit that discards values until the first one satisfying the predicate,
+/// and then forwards that value and all subsequent ones.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::drop_until;
+///
+/// let src = Source::from_iter(vec![1, 2, 0, 3]).fuse(drop_until(|x| *x == 0));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![0, 3]);
+/// ```
+pub fn drop_until<'a, T: 'static, F>(mut pred: F) -> Conduit<'a, T, T>
+    where F: 'a + FnMut(&T) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => if pred(&t) {
+                produce(t).and(Conduit::transform(|t| t))
+            } else {
+                drop_until(pred)
+            }
+        }
+    })
+}
+
+/// A conduit that produces all overlapping pairs of consecutive values.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::pairs;
+///
+/// let src = Source::from_iter(vec![1, 2, 3]).fuse(pairs());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![(1, 2), (2, 3)]);
+/// ```
+pub fn pairs<'a, T: 'static + Clone>() -> Conduit<'a, T, (T, T)> {
+    consume().and_then(|io| {
+        match io {
+            None => ().into(),
+            Some(t) => pairs_from(t)
+        }
+    })
+}
+
+fn pairs_from<'a, T: 'static + Clone>(prev: T) -> Conduit<'a, T, (T, T)> {
+    consume().and_then(move |io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => produce((prev, t.clone())).and(pairs_from(t))
+        }
+    })
+}
+
+/// A conduit that splits the stream into segments at separator values,
+/// analogous to `str::split`.
+///
+/// Separators are not included in the segments, and consecutive
+/// separators produce empty segments. An empty stream produces no segments.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::separate_by;
+///
+/// let src = Source::from_iter(vec![1, 0, 2, 3, 0, 0, 4]).fuse(separate_by(|x| *x == 0));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![vec![1], vec![2, 3], vec![], vec![4]]);
+/// ```
+pub fn separate_by<'a, T: 'static, F>(is_sep: F) -> Conduit<'a, T, Vec<T>>
+    where F: 'a + FnMut(&T) -> bool {
+    consume().and_then(|io| {
+        match io {
+            None => ().into(),
+            Some(t) => leftover(t).and(separate_by_from(Vec::new(), is_sep))
+        }
+    })
+}
+
+fn separate_by_from<'a, T: 'static, F>(mut acc: Vec<T>, mut is_sep: F) -> Conduit<'a, T, Vec<T>>
+    where F: 'a + FnMut(&T) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => produce(acc),
+            Some(t) => if is_sep(&t) {
+                produce(acc).and(separate_by_from(Vec::new(), is_sep))
+            } else {
+                charge::<T>(1);
+                acc.push(t);
+                separate_by_from(acc, is_sep)
+            }
+        }
+    })
+}
+
+/// Returns the given source, or a source that produces nothing if there is none.
+pub fn or_empty<'a, O: 'static>(source: Option<Source<'a, O>>) -> Source<'a, O> {
+    match source {
+        None => ().into(),
+        Some(src) => src
+    }
+}
+
+/// Returns the values of `primary`, or, if it terminates without producing
+/// any value, the values of `fallback`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::or;
+///
+/// let src = or(Source::from_iter(vec![]), Source::from_iter(vec![42]));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![42]);
+/// let src = or(Source::from_iter(vec![1]), Source::from_iter(vec![42]));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1]);
+/// ```
+pub fn or<'a, O: 'static>(primary: Source<'a, O>, fallback: Source<'a, O>) -> Source<'a, O> {
+    or_from(SourceIter::new(primary), fallback)
+}
+
+fn or_from<'a, O: 'static>(mut iter: SourceIter<'a, O>, fallback: Source<'a, O>) -> Source<'a, O> {
+    defer().and_then(move |_| {
+        match iter.step() {
+            Step::Ready(o) => produce(o).and(drain(iter)),
+            Step::Pending(parked) => {
+                if parked {
+                    park();
+                }
+                or_from(iter, fallback)
+            },
+            Step::Done => fallback
+        }
+    })
+}
+
+fn drain<'a, O: 'static>(mut iter: SourceIter<'a, O>) -> Source<'a, O> {
+    defer().and_then(move |_| {
+        match iter.step() {
+            Step::Ready(o) => produce(o).and(drain(iter)),
+            Step::Pending(parked) => {
+                if parked {
+                    park();
+                }
+                drain(iter)
+            },
+            Step::Done => ().into()
+        }
+    })
+}
+
+/// A conduit that forwards the elements of each incoming vector individually.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::flatten_vec;
+///
+/// let src = Source::from_iter(vec![vec![1, 2], vec![], vec![3]]).fuse(flatten_vec());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+/// ```
+pub fn flatten_vec<'a, T: 'static>() -> Conduit<'a, Vec<T>, T> {
+    consume().and_then(|io: Option<Vec<T>>| {
+        match io {
+            None => ().into(),
+            Some(v) => if v.is_empty() {
+                flatten_vec()
+            } else {
+                produce_chunk(v).and(flatten_vec())
+            }
+        }
+    })
+}
+
+/// A conduit that produces the growing prefixes of the stream,
+/// i.e. `[e0]`, `[e0, e1]`, `[e0, e1, e2]`, and so on.
+///
+/// Note that all values seen so far are retained, so memory usage
+/// grows linearly with the length of the stream, and each prefix
+/// is a fresh copy.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::accumulate;
+///
+/// let src = Source::from_iter(vec![1, 2, 3]).fuse(accumulate());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![vec![1], vec![1, 2], vec![1, 2, 3]]);
+/// ```
+pub fn accumulate<'a, T: 'static + Clone>() -> Conduit<'a, T, Vec<T>> {
+    accumulate_by(Vec::new(), |mut v: Vec<T>, t| {
+        // Every step hands a copy of the whole vector downstream.
+        charge::<T>(v.len() + 1);
+        v.push(t);
+        v
+    })
+}
+
+/// A conduit that folds each value into an accumulator,
+/// and produces a copy of the accumulator after every step.
+pub fn accumulate_by<'a, T: 'static, S: 'static + Clone, F>(init: S, mut f: F) -> Conduit<'a, T, S>
+    where F: 'a + FnMut(S, T) -> S {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => {
+                let s = f(init, t);
+                produce(s.clone()).and(accumulate_by(s, f))
+            }
+        }
+    })
+}
+
+/// A conduit that forwards the values of `Ok` results,
+/// and terminates on the first `Err`, discarding the error.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::flatten_result;
+///
+/// let src: Source<Result<i32, &str>> = Source::from_iter(vec![Ok(1), Ok(2), Err("boom"), Ok(3)]);
+/// assert_eq!(src.fuse(flatten_result()).into_iter().collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub fn flatten_result<'a, T: 'static, E: 'static>() -> Conduit<'a, Result<T, E>, T> {
+    flatten_result_report().map(|_| ())
+}
+
+/// A conduit that forwards the values of `Ok` results,
+/// and terminates on the first `Err`, returning the error.
+pub fn flatten_result_report<'a, T: 'static, E: 'static>() -> ConduitM<'a, Result<T, E>, T, Result<(), E>> {
+    consume().and_then(|io| {
+        match io {
+            None => Ok(()).into(),
+            Some(Ok(t)) => produce(t).and(flatten_result_report()),
+            Some(Err(e)) => Err(e).into()
+        }
+    })
+}
+
+/// Turns a conduit producing results into one producing the `Ok` values,
+/// which terminates on the first `Err` and returns it.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Conduit};
+/// use plumbum::extra::yield_result;
+///
+/// let parse: Conduit<&str, Result<i32, &str>> = Conduit::transform(|s: &str| s.parse().map_err(|_| s));
+/// let src = Source::from_iter(vec!["1", "x", "2"]).fuse(yield_result(parse).map(|res| assert_eq!(res, Err("x"))));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1]);
+/// ```
+pub fn yield_result<'a, I: 'static, T: 'static, E: 'static>(result_conduit: ConduitM<'a, I, Result<T, E>, ()>)
+    -> ConduitM<'a, I, T, Result<(), E>> {
+    result_conduit.fuse(flatten_result_report())
+}
+
+/// A conduit that forwards the values of `Some` options,
+/// and terminates on the first `None`.
+pub fn transpose_option<'a, T: 'static>() -> Conduit<'a, Option<T>, T> {
+    consume().and_then(|io| {
+        match io {
+            Some(Some(t)) => produce(t).and(transpose_option()),
+            _ => ().into()
+        }
+    })
+}
+
+/// Runs the source until it produces `None` or is exhausted,
+/// forwarding the values of the `Some` options.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::while_some;
+///
+/// let src = Source::from_iter(vec![Some(1), Some(2), None, Some(3)]);
+/// assert_eq!(while_some(src).into_iter().collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub fn while_some<'a, T: 'static>(source: Source<'a, Option<T>>) -> Source<'a, T> {
+    source.fuse(transpose_option())
+}
+
+/// A conduit that looks up each incoming key in the given map.
+///
+/// # Example
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::hash_map_lookup;
+///
+/// let map = HashMap::from_iter(vec![(1, "one"), (2, "two")]);
+/// let src = Source::from_iter(vec![2, 3, 1]).fuse(hash_map_lookup(map));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![Some("two"), None, Some("one")]);
+/// ```
+pub fn hash_map_lookup<'a, K, V>(map: HashMap<K, V>) -> Conduit<'a, K, Option<V>>
+    where K: 'static + Eq + Hash, V: 'static + Clone {
+    Conduit::transform(move |k| map.get(&k).cloned())
+}
+
+/// A conduit that looks up each incoming key in the given map,
+/// producing `default` for keys that are not in the map.
+pub fn lookup_or_default<'a, K, V>(map: HashMap<K, V>, default: V) -> Conduit<'a, K, V>
+    where K: 'static + Eq + Hash, V: 'static + Clone {
+    Conduit::transform(move |k| map.get(&k).unwrap_or(&default).clone())
+}
+
+/// A conduit that pairs each value with the number of values seen so far,
+/// counting from 1, e.g. for progress reporting.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::count_conduit;
+///
+/// let src = Source::from_iter(vec!['a', 'b']).fuse(count_conduit());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![(1, 'a'), (2, 'b')]);
+/// ```
+pub fn count_conduit<'a, T: 'static>() -> Conduit<'a, T, (usize, T)> {
+    map_accum_l(0, |n, t| (n + 1, (n + 1, t)))
+}
+
+/// A sink that returns the last `n` values from upstream, in their
+/// original order, analogous to `tail -n`.
+///
+/// At most `n` values are buffered, regardless of the length of the stream.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::take_last;
+///
+/// assert_eq!(Source::from_iter(vec![1, 2, 3, 4]).connect(take_last(2)), vec![3, 4]);
+/// assert_eq!(Source::from_iter(vec![1]).connect(take_last(2)), vec![1]);
+/// ```
+pub fn take_last<'a, T: 'static>(n: usize) -> Sink<'a, T, Vec<T>> {
+    Sink::fold(VecDeque::with_capacity(n), move |mut buf, t| {
+        if n > 0 {
+            if buf.len() == n {
+                buf.pop_front();
+            } else {
+                charge::<T>(1);
+            }
+            buf.push_back(t);
+        }
+        buf
+    }).map(|buf| buf.into_iter().collect())
+}
+
+/// A conduit that forwards all values unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::identity;
+///
+/// let src = Source::from_iter(vec![1, 2]).fuse(identity());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub fn identity<'a, T: 'static>() -> Conduit<'a, T, T> {
+    Conduit::transform(|t| t)
+}
+
+/// A conduit that forwards all values except the last `n`,
+/// analogous to `head -n -k`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::skip_last;
+///
+/// let src = Source::from_iter(vec![1, 2, 3, 4]).fuse(skip_last(2));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub fn skip_last<'a, T: 'static>(n: usize) -> Conduit<'a, T, T> {
+    skip_last_from(n, VecDeque::with_capacity(n))
+}
+
+fn skip_last_from<'a, T: 'static>(n: usize, mut buf: VecDeque<T>) -> Conduit<'a, T, T> {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(t) => {
+                buf.push_back(t);
+                if buf.len() > n {
+                    let t = buf.pop_front().unwrap();
+                    produce(t).and(skip_last_from(n, buf))
+                } else {
+                    charge::<T>(1);
+                    skip_last_from(n, buf)
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that produces the minimum of all values seen so far.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::running_min;
+///
+/// let src = Source::from_iter(vec![3, 4, 1, 2]).fuse(running_min());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![3, 3, 1, 1]);
+/// ```
+pub fn running_min<'a, T: 'static + Ord + Clone>() -> Conduit<'a, T, T> {
+    running_by(::std::cmp::min)
+}
+
+/// A conduit that produces the maximum of all values seen so far.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::running_max;
+///
+/// let src = Source::from_iter(vec![3, 4, 1, 5]).fuse(running_max());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![3, 4, 4, 5]);
+/// ```
+pub fn running_max<'a, T: 'static + Ord + Clone>() -> Conduit<'a, T, T> {
+    running_by(::std::cmp::max)
+}
+
+fn running_by<'a, T: 'static + Clone>(f: fn(T, T) -> T) -> Conduit<'a, T, T> {
+    map_accum_l(None, move |acc, t| {
+        let next = match acc {
+            None => t,
+            Some(prev) => f(prev, t)
+        };
+        (Some(next.clone()), next)
+    })
+}
+
+/// A conduit that applies `f` to each pair of consecutive values,
+/// as `f(&prev, &curr)`. Nothing is produced for the first value.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::diff;
+///
+/// let src = Source::from_iter(vec![1, 4, 9]).fuse(diff(|prev, curr| prev < curr));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![true, true]);
+/// ```
+pub fn diff<'a, T: 'static + Clone, D: 'static, F>(f: F) -> Conduit<'a, T, D>
+    where F: 'a + FnMut(&T, &T) -> D {
+    consume().and_then(|io| {
+        match io {
+            None => ().into(),
+            Some(t) => diff_from(t, f)
+        }
+    })
+}
+
+fn diff_from<'a, T: 'static + Clone, D: 'static, F>(prev: T, mut f: F) -> Conduit<'a, T, D>
+    where F: 'a + FnMut(&T, &T) -> D {
+    consume().and_then(move |io: Option<T>| {
+        match io {
+            None => ().into(),
+            Some(t) => {
+                let d = f(&prev, &t);
+                produce(d).and(diff_from(t, f))
+            }
+        }
+    })
+}
+
+/// A conduit that produces the differences `curr - prev`
+/// between consecutive values.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::diff_sub;
+///
+/// let src = Source::from_iter(vec![1, 4, 9]).fuse(diff_sub());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![3, 5]);
+/// ```
+pub fn diff_sub<'a, T: 'static + Clone + Sub<Output = T>>() -> Conduit<'a, T, T> {
+    diff(|prev: &T, curr: &T| curr.clone() - prev.clone())
+}
+
+/// A conduit that forwards `o` for each value for which `f` returns `Some(o)`,
+/// and terminates on the first value for which it returns `None`.
+/// That value is consumed and discarded.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::take_while_map;
+///
+/// let src = Source::from_iter(vec!["1", "2", "x", "3"])
+///     .fuse(take_while_map(|s: &str| s.parse::<i32>().ok()));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub fn take_while_map<'a, I: 'static, O: 'static, F>(mut f: F) -> Conduit<'a, I, O>
+    where F: 'a + FnMut(I) -> Option<O> {
+    consume().and_then(move |io| {
+        match io.and_then(&mut f) {
+            None => ().into(),
+            Some(o) => produce(o).and(take_while_map(f))
+        }
+    })
+}
+
+/// A conduit that expands each value into the `Some` items of the
+/// iterator returned by `f`, skipping the `None` items.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::concat_filter_map;
+///
+/// let src = Source::from_iter(vec!["1 x 2", "3"])
+///     .fuse(concat_filter_map(|s: &str| s.split(' ').map(|w| w.parse::<i32>().ok()).collect::<Vec<_>>()));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+/// ```
+pub fn concat_filter_map<'a, I: 'static, O: 'static, F, It>(mut f: F) -> Conduit<'a, I, O>
+    where F: 'a + FnMut(I) -> It, It: IntoIterator<Item = Option<O>> {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(i) => {
+                let chunk: Vec<O> = f(i).into_iter().flatten().collect();
+                if chunk.is_empty() {
+                    concat_filter_map(f)
+                } else {
+                    produce_chunk(chunk).and(concat_filter_map(f))
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that threads a state through the stream like `map_accum_l`,
+/// and terminates once `pred` holds for the new state. The output of the
+/// step that satisfied the predicate is still produced.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::scan_until;
+///
+/// let src = Source::from_iter(vec![1, 2, 3, 4]).fuse(scan_until(0, |s, x| (s + x, s + x), |s| *s >= 3));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 3]);
+/// ```
+pub fn scan_until<'a, I: 'static, S: 'a, O: 'static, F, P>(init: S, mut step: F, mut pred: P) -> Conduit<'a, I, O>
+    where F: 'a + FnMut(S, I) -> (S, O), P: 'a + FnMut(&S) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(i) => {
+                let (s, o) = step(init, i);
+                if pred(&s) {
+                    produce(o)
+                } else {
+                    produce(o).and(scan_until(s, step, pred))
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that pairs each value with its zero-based index, counted
+/// as a `u64`, so that the index cannot overflow on 32-bit platforms.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::zip_index_u64;
+///
+/// let src = Source::from_iter(vec!['a', 'b']).fuse(zip_index_u64());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![(0, 'a'), (1, 'b')]);
+/// ```
+pub fn zip_index_u64<'a, T: 'static>() -> Conduit<'a, T, (u64, T)> {
+    map_accum_l(0u64, |n, t| (n + 1, (n, t)))
+}
+
+/// A conduit that groups values into vectors of `n` values each.
+/// The last vector is shorter if the stream does not divide evenly.
+///
+/// # Panics
+///
+/// Panics if `n` is zero.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::chunk;
+///
+/// let src = Source::from_iter(vec![1, 2, 3]).fuse(chunk(2));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![vec![1, 2], vec![3]]);
+/// ```
+pub fn chunk<'a, T: 'static>(n: usize) -> Conduit<'a, T, Vec<T>> {
+    assert!(n > 0, "chunk size must be greater than zero");
+    chunk_from(n, Vec::with_capacity(n))
+}
+
+fn chunk_from<'a, T: 'static>(n: usize, mut acc: Vec<T>) -> Conduit<'a, T, Vec<T>> {
+    consume().and_then(move |io| {
+        match io {
+            None => if acc.is_empty() { ().into() } else { produce(acc) },
+            Some(t) => {
+                charge::<T>(1);
+                acc.push(t);
+                if acc.len() == n {
+                    produce(acc).and(chunk_from(n, Vec::with_capacity(n)))
+                } else {
+                    chunk_from(n, acc)
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that folds all values from upstream, and produces
+/// the final accumulated value once upstream is exhausted.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::fold_conduit;
+///
+/// let src = Source::from_iter(vec![1, 2, 3]).fuse(fold_conduit(0, |x, y| x + y));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![6]);
+/// ```
+pub fn fold_conduit<'a, I: 'static, S: 'static, F>(init: S, mut f: F) -> Conduit<'a, I, S>
+    where F: 'a + FnMut(S, I) -> S {
+    Sink::sink(init, move |s, i| Ok(f(s, i))).to_consumer().and_then(produce)
+}
+
+/// A conduit that groups values into batches, flushing a batch once it
+/// holds `max_size` values, or once `is_flush` holds for a value.
+/// The value that triggered the flush is the last one in its batch.
+///
+/// # Panics
+///
+/// Panics if `max_size` is zero.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::batch_by_sentinel;
+///
+/// let src = Source::from_iter(vec![1, 0, 2, 3, 4, 5]).fuse(batch_by_sentinel(3, |x| *x == 0));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![vec![1, 0], vec![2, 3, 4], vec![5]]);
+/// ```
+pub fn batch_by_sentinel<'a, T: 'static, F>(max_size: usize, is_flush: F) -> Conduit<'a, T, Vec<T>>
+    where F: 'a + FnMut(&T) -> bool {
+    assert!(max_size > 0, "batch size must be greater than zero");
+    batch_by_sentinel_from(max_size, Vec::new(), is_flush)
+}
+
+fn batch_by_sentinel_from<'a, T: 'static, F>(max_size: usize, mut acc: Vec<T>, mut is_flush: F) -> Conduit<'a, T, Vec<T>>
+    where F: 'a + FnMut(&T) -> bool {
+    consume().and_then(move |io| {
+        match io {
+            None => if acc.is_empty() { ().into() } else { produce(acc) },
+            Some(t) => {
+                let flush = is_flush(&t);
+                charge::<T>(1);
+                acc.push(t);
+                if flush || acc.len() == max_size {
+                    produce(acc).and(batch_by_sentinel_from(max_size, Vec::new(), is_flush))
+                } else {
+                    batch_by_sentinel_from(max_size, acc, is_flush)
+                }
+            }
+        }
+    })
+}
+
+/// A conduit that keeps only the strings that match the pattern in full.
+///
+/// The pattern is compiled up front, so that an invalid pattern
+/// is reported before the pipeline runs.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::regex_filter;
+///
+/// let words = vec!["ab".to_string(), "a".to_string(), "ba".to_string()];
+/// let src = Source::from_iter(words).fuse(regex_filter("a").unwrap());
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec!["a"]);
+/// assert!(regex_filter("(a").is_err());
+/// ```
+#[cfg(feature = "regex")]
+pub fn regex_filter<'a>(pattern: &str) -> Result<Conduit<'a, String, String>, ::regex::Error> {
+    let re = ::regex::Regex::new(&format!("^(?:{})$", pattern))?;
+    Ok(regex_keep(re))
+}
+
+#[cfg(feature = "regex")]
+fn regex_keep<'a>(re: ::regex::Regex) -> Conduit<'a, String, String> {
+    consume().and_then(move |io: Option<String>| {
+        match io {
+            None => ().into(),
+            Some(s) => if re.is_match(&s) {
+                produce(s).and(regex_keep(re))
+            } else {
+                regex_keep(re)
+            }
+        }
+    })
+}
+
+/// A conduit that deserialises each incoming CSV row.
+///
+/// If `has_headers` is set, the first row is used as the header row,
+/// which allows the fields of `T` to be matched by name.
+/// If the header row cannot be parsed, its error is produced,
+/// and the conduit terminates without decoding any further rows.
+///
+/// # Example
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::csv_decode;
+///
+/// let rows = || Source::from_iter(vec!["name,age".to_string(), "ada,36".to_string()]);
+///
+/// let src = rows().fuse(csv_decode::<(String, String)>(false));
+/// let records: Vec<_> = src.into_iter().map(Result::unwrap).collect();
+/// assert_eq!(records, vec![("name".to_string(), "age".to_string()), ("ada".to_string(), "36".to_string())]);
+///
+/// let src = rows().fuse(csv_decode::<HashMap<String, String>>(true));
+/// let records: Vec<_> = src.into_iter().map(Result::unwrap).collect();
+/// assert_eq!(records.len(), 1);
+/// assert_eq!(records[0]["age"], "36");
+/// ```
+#[cfg(feature = "csv")]
+pub fn csv_decode<'a, T>(has_headers: bool) -> Conduit<'a, String, Result<T, ::csv::Error>>
+    where T: 'static + ::serde::de::DeserializeOwned {
+    if has_headers {
+        csv_decode_headers()
+    } else {
+        csv_decode_with(None)
+    }
+}
+
+#[cfg(feature = "csv")]
+fn csv_record(row: &str) -> ::csv::Result<::csv::StringRecord> {
+    let mut record = ::csv::StringRecord::new();
+    ::csv::ReaderBuilder::new().has_headers(false).from_reader(row.as_bytes()).read_record(&mut record)?;
+    Ok(record)
+}
+
+#[cfg(feature = "csv")]
+fn csv_decode_headers<'a, T>() -> Conduit<'a, String, Result<T, ::csv::Error>>
+    where T: 'static + ::serde::de::DeserializeOwned {
+    consume().and_then(|io: Option<String>| {
+        match io {
+            None => ().into(),
+            Some(row) => match csv_record(&row) {
+                Err(e) => produce(Err(e)),
+                Ok(headers) => csv_decode_with(Some(headers))
+            }
+        }
+    })
+}
+
+#[cfg(feature = "csv")]
+fn csv_decode_with<'a, T>(headers: Option<::csv::StringRecord>) -> Conduit<'a, String, Result<T, ::csv::Error>>
+    where T: 'static + ::serde::de::DeserializeOwned {
+    consume().and_then(move |io: Option<String>| {
+        match io {
+            None => ().into(),
+            Some(row) => {
+                let res = csv_record(&row).and_then(|record| record.deserialize(headers.as_ref()));
+                produce(res).and(csv_decode_with(headers))
+            }
+        }
+    })
+}
+
+/// A sink that serialises the values it consumes as CSV rows,
+/// and writes them to the given `Write`.
+///
+/// If `has_headers` is set, a header row is derived from the field names of `T`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+/// use plumbum::extra::{csv_decode, csv_encode};
+///
+/// let mut out = Vec::new();
+/// let res = Source::from_iter(vec![("ada", 36), ("alan", 41)]).connect(csv_encode(&mut out, false));
+/// assert!(res.is_ok());
+/// let text = String::from_utf8(out).unwrap();
+/// assert_eq!(text, "ada,36\nalan,41\n");
+///
+/// let rows = Source::from_iter(text.lines().map(String::from).collect::<Vec<_>>());
+/// let records: Vec<(String, u32)> = rows.fuse(csv_decode(false)).into_iter().map(Result::unwrap).collect();
+/// assert_eq!(records, vec![("ada".to_string(), 36), ("alan".to_string(), 41)]);
+/// ```
+#[cfg(feature = "csv")]
+pub fn csv_encode<'a, T, W>(w: W, has_headers: bool) -> Sink<'a, T, ::csv::Result<()>>
+    where T: 'static + ::serde::Serialize, W: 'a + ::std::io::Write {
+    csv_write(::csv::WriterBuilder::new().has_headers(has_headers).from_writer(w))
+}
+
+#[cfg(feature = "csv")]
+fn csv_write<'a, T, W>(mut w: ::csv::Writer<W>) -> Sink<'a, T, ::csv::Result<()>>
+    where T: 'static + ::serde::Serialize, W: 'a + ::std::io::Write {
+    consume().and_then(move |io| {
+        match io {
+            None => w.flush().map_err(::csv::Error::from).into(),
+            Some(t) => match w.serialize(t) {
+                Err(e) => Err(e).into(),
+                Ok(_) => csv_write(w)
+            }
+        }
+    })
+}
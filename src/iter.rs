@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+use std::iter;
+
+use super::{ConduitM, Chunk, Source, Void, defer, park, produce, produce_chunk, take_parked};
+
+/// An iterator over the values produced by a `Source`.
+///
+/// The source is driven lazily, one step at a time,
+/// only as far as needed to produce the next value.
+/// Iterating does not return while the source defers, so sources waiting
+/// for an asynchronous event, like `tokio_receiver_source`, block it.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::*;
+///
+/// let src: Source<i32> = defer().and(produce(1)).and(produce(2));
+/// let v: Vec<_> = src.into_iter().map(|x| x * 10).collect();
+/// assert_eq!(v, vec![10, 20]);
+/// ```
+pub struct SourceIter<'a, O> {
+    inner: Option<Source<'a, O>>,
+    buffer: Vec<O>
+}
+
+impl<'a, O> SourceIter<'a, O> {
+    /// Creates an iterator over the values produced by the given source.
+    pub fn new(src: Source<'a, O>) -> Self {
+        SourceIter { inner: Some(src), buffer: Vec::new() }
+    }
+
+    /// Turns the remaining values back into a source.
+    fn into_source(mut self) -> Source<'a, O> where O: 'a {
+        let rest = self.inner.unwrap_or_else(|| ().into());
+        if self.buffer.is_empty() {
+            rest
+        } else {
+            self.buffer.reverse();
+            produce_chunk(self.buffer).and(rest)
+        }
+    }
+}
+
+/// The outcome of advancing a `SourceIter` without blocking, see `SourceIter::step`.
+pub(crate) enum Step<O> {
+    /// The source produced a value.
+    Ready(O),
+    /// The source deferred. The flag tells whether it parked the task,
+    /// i.e. registered it with something that will wake it.
+    Pending(bool),
+    /// The source terminated.
+    Done
+}
+
+impl<'a, O: 'static> SourceIter<'a, O> {
+    /// Drives the source until it produces a value, terminates, or defers.
+    ///
+    /// Combinators that are built on top of `SourceIter` use this to pass the
+    /// defers of their sources on, so that they can be driven asynchronously.
+    pub(crate) fn step(&mut self) -> Step<O> {
+        loop {
+            // The buffer is kept in reverse order, so that values can be popped off the back.
+            if let Some(o) = self.buffer.pop() {
+                return Step::Ready(o);
+            }
+            let src = match self.inner.take() {
+                None => return Step::Done,
+                Some(src) => src
+            };
+            self.inner = match src {
+                ConduitM::Pure(_) => None,
+                ConduitM::Defer(k) => {
+                    let outer = take_parked();
+                    self.inner = Some(k.run(()));
+                    let parked = take_parked();
+                    if outer {
+                        park();
+                    }
+                    return Step::Pending(parked);
+                },
+                ConduitM::Flush(k) => Some(k.run(())),
+                ConduitM::Await(k) => Some(k.run(Chunk::Chunk(Vec::new()))),
+                ConduitM::Yield(mut o, k) => if o.is_empty() {
+                    Some(k.run(()))
+                } else {
+                    // Keep the continuation behind an empty chunk,
+                    // so that it only runs once the buffer is exhausted.
+                    o.reverse();
+                    self.buffer = o;
+                    Some(ConduitM::Yield(Vec::new(), k))
+                },
+                ConduitM::Leftover(_, k) => Some(k.run(()))
+            };
+        }
+    }
+}
+
+impl<'a, O: 'static> Iterator for SourceIter<'a, O> {
+    type Item = O;
+
+    fn next(&mut self) -> Option<O> {
+        loop {
+            match self.step() {
+                Step::Ready(o) => return Some(o),
+                Step::Pending(_) => (),
+                Step::Done => return None
+            }
+        }
+    }
+}
+
+impl<'a, O: 'static> IntoIterator for ConduitM<'a, Void, O, ()> {
+    type Item = O;
+    type IntoIter = SourceIter<'a, O>;
+
+    fn into_iter(self) -> SourceIter<'a, O> {
+        SourceIter::new(self)
+    }
+}
+
+/// A source with lookahead, which can inspect upcoming values
+/// before handing the rest of the stream on as a `Source`.
+///
+/// Peeking blocks while the source defers, like iterating over a `SourceIter`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::Source;
+///
+/// let mut src = Source::from_iter(vec![1, 2, 3]).peekable();
+/// assert_eq!(src.peek(), Some(&1));
+/// assert_eq!(src.peek_n(2), &[1, 2]);
+/// assert_eq!(src.into_source().into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+/// ```
+pub struct Peekable<'a, O> {
+    source: SourceIter<'a, O>,
+    buffer: VecDeque<O>
+}
+
+impl<'a, O: 'static> Peekable<'a, O> {
+    /// Creates a peekable source from the given source.
+    pub fn new(src: Source<'a, O>) -> Self {
+        Peekable { source: SourceIter::new(src), buffer: VecDeque::new() }
+    }
+
+    /// Returns a reference to the next value, without consuming it.
+    pub fn peek(&mut self) -> Option<&O> {
+        self.peek_n(1).first()
+    }
+
+    /// Returns up to `n` upcoming values, pulling them from the source
+    /// as needed. Fewer values are returned if the source terminates.
+    pub fn peek_n(&mut self, n: usize) -> &[O] {
+        while self.buffer.len() < n {
+            match self.source.next() {
+                None => break,
+                Some(o) => self.buffer.push_back(o)
+            }
+        }
+        let len = ::std::cmp::min(n, self.buffer.len());
+        &self.buffer.make_contiguous()[..len]
+    }
+
+    /// Turns the peekable source back into a `Source`,
+    /// starting with the values that have been peeked at.
+    pub fn into_source(self) -> Source<'a, O> {
+        let rest = self.source.into_source();
+        if self.buffer.is_empty() {
+            rest
+        } else {
+            produce_chunk(self.buffer.into_iter().collect()).and(rest)
+        }
+    }
+}
+
+impl<'a, O: 'static> ConduitM<'a, Void, O, ()> {
+    /// Wraps the source for lookahead, see `Peekable`.
+    pub fn peekable(self) -> Peekable<'a, O> {
+        Peekable::new(self)
+    }
+}
+
+/// A type-erased source.
+///
+/// Unlike `Source`, which is a concrete program, a `DynSource` only
+/// knows how to pull its next value, which makes it convenient to keep
+/// sources built from different pipelines in the same collection.
+/// Pulling blocks while the source defers, like iterating over a `SourceIter`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{DynSource, Source, produce};
+///
+/// let srcs: Vec<DynSource<i32>> = vec![
+///     produce(1).into_dyn(),
+///     Source::from_iter(vec![2, 3]).into_dyn()
+/// ];
+/// let v: Vec<_> = srcs.into_iter().flat_map(|s| s).collect();
+/// assert_eq!(v, vec![1, 2, 3]);
+/// ```
+pub struct DynSource<'a, O>(Box<dyn FnMut() -> Option<O> + 'a>);
+
+impl<'a, O: 'a> DynSource<'a, O> {
+    /// Turns the type-erased source back into a `Source`.
+    pub fn into_source(mut self) -> Source<'a, O> {
+        defer().and_then(move |_| {
+            match (self.0)() {
+                None => ().into(),
+                Some(o) => produce(o).and(self.into_source())
+            }
+        })
+    }
+}
+
+impl<'a, O> IntoIterator for DynSource<'a, O> {
+    type Item = O;
+    type IntoIter = iter::FromFn<Box<dyn FnMut() -> Option<O> + 'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        iter::from_fn(self.0)
+    }
+}
+
+impl<'a, O: 'static> ConduitM<'a, Void, O, ()> {
+    /// Erases the concrete type of the source.
+    pub fn into_dyn(self) -> DynSource<'a, O> {
+        let mut it = self.into_iter();
+        DynSource(Box::new(move || it.next()))
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::mem::transmute;
 use std::marker::PhantomData;
 use std::collections::VecDeque;
@@ -21,7 +22,9 @@ impl<'a, A, B> FnTake<'a, A, B> {
 /// The Kleisli arrow from `A` to `ConduitM<I, O, B>`.
 pub struct Kleisli<'a, A, I, O, B> {
     phan: PhantomData<(A, B)>,
-    deque: VecDeque<FnTake<'a, Box<()>, ConduitM<'a, I, O, ()>>>
+    deque: VecDeque<FnTake<'a, Box<()>, ConduitM<'a, I, O, ()>>>,
+    /// Bounds on the number of values the arrow yields, as in `Iterator::size_hint`.
+    hint: (usize, Option<usize>)
 }
 
 unsafe fn fn_transmute<'a, I, O, A, B, F: 'a + FnOnce(Box<A>) -> ConduitM<'a, I, O, B>>(f: F)
@@ -41,15 +44,33 @@ impl<'a, I, O, A> Kleisli<'a, A, I, O, A> {
     /// assert_eq!(k.run(42), 42.into());
     /// ```
     pub fn new() -> Kleisli<'a, A, I, O, A> {
-        Kleisli { phan: PhantomData, deque: VecDeque::new() }
+        Kleisli { phan: PhantomData, deque: VecDeque::new(), hint: (0, Some(0)) }
     }
 }
 
+/// The depth beyond which an arrow is assumed to have been built by accident,
+/// e.g. by repeatedly appending to the same arrow in a loop.
+const MAX_DEPTH: usize = 10_000;
+
 pub fn append_boxed<'a, I, O, A, B, C, F>
     (mut k: Kleisli<'a, A, I, O, B>, f: F) -> Kleisli<'a, A, I, O, C>
     where F: 'a + FnOnce(Box<B>) -> ConduitM<'a, I, O, C> {
+    debug_assert!(k.deque.len() < MAX_DEPTH,
+                  "Kleisli arrow exceeds a depth of {} continuations", MAX_DEPTH);
     k.deque.push_back(unsafe { fn_transmute(f) });
-    Kleisli { phan: PhantomData, deque: k.deque }
+    Kleisli { phan: PhantomData, deque: k.deque, hint: (0, None) }
+}
+
+/// Records bounds on the number of values the arrow yields.
+/// They are forgotten as soon as another continuation is appended.
+pub fn with_hint<'a, I, O, A, B>(mut k: Kleisli<'a, A, I, O, B>, hint: (usize, Option<usize>))
+    -> Kleisli<'a, A, I, O, B> {
+    k.hint = hint;
+    k
+}
+
+pub fn hint<I, O, A, B>(k: &Kleisli<A, I, O, B>) -> (usize, Option<usize>) {
+    k.hint
 }
 
 impl<'a, I, O, A, B> Kleisli<'a, A, I, O, B> {
@@ -69,6 +90,23 @@ impl<'a, I, O, A, B> Kleisli<'a, A, I, O, B> {
         Kleisli::new().append(f)
     }
 
+    /// Wraps the given function into an arrow.
+    /// This is equivalent to `Kleisli::new().append(f)` and `Kleisli::from(f)`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::Kleisli;
+    ///
+    /// let k: Kleisli<i32, (), (), i32> = Kleisli::new_with(|x: i32| (x + 1).into());
+    /// assert_eq!(k.depth(), 1);
+    /// assert_eq!(k.run(42), 43.into());
+    /// ```
+    pub fn new_with<F>(f: F) -> Kleisli<'a, A, I, O, B>
+        where F: 'a + FnOnce(A) -> ConduitM<'a, I, O, B> {
+        Kleisli::new().append(f)
+    }
+
     /// Appends the given function to the tail of the arrow.
     /// This corresponds to closure composition at the codomain (post-composition).
     ///
@@ -86,6 +124,21 @@ impl<'a, I, O, A, B> Kleisli<'a, A, I, O, B> {
         append_boxed(self, move |b| f(*b))
     }
 
+    /// Returns the number of continuations in the arrow.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::Kleisli;
+    ///
+    /// let k: Kleisli<i32, (), (), i32> = Kleisli::from(|x: i32| (x + 1).into())
+    ///                                    .append(|x| (x * 2).into());
+    /// assert_eq!(k.depth(), 2);
+    /// ```
+    pub fn depth(&self) -> usize {
+        self.deque.len()
+    }
+
     /// Given an input, runs the arrow to completion and return
     /// the resulting program.
     pub fn run(mut self, a: A) -> ConduitM<'a, I, O, B> where I: 'static, O: 'static {
@@ -102,6 +155,12 @@ impl<'a, I, O, A, B> Kleisli<'a, A, I, O, B> {
 
 }
 
+impl<'a, A, I, O, B> fmt::Debug for Kleisli<'a, A, I, O, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Kleisli(depth={})", self.depth())
+    }
+}
+
 #[test]
 fn kleisli_run_plus_one() {
     let k: Kleisli<i32, (), (), i32> = Kleisli::from(|a: i32| (a + 1).into());
@@ -114,3 +173,22 @@ fn kleisli_run_to_string() {
         Kleisli::from(|a: i32| (a.to_string()).into());
     assert_eq!(k.run(42), "42".to_string().into());
 }
+
+#[test]
+fn kleisli_debug_depth() {
+    let k: Kleisli<i32, (), (), i32> = Kleisli::new();
+    assert_eq!(format!("{:?}", k), "Kleisli(depth=0)");
+    let k = k.append(|a| (a + 1).into()).append(|a| (a + 1).into());
+    assert_eq!(format!("{:?}", k), "Kleisli(depth=2)");
+}
+
+#[test]
+fn kleisli_long_map_chain() {
+    use super::{consume, Source};
+    use std::iter::FromIterator;
+    let mut sink = consume().map(|x: Option<i32>| x.unwrap_or(0));
+    for _ in 0..5000 {
+        sink = sink.map(|x| x + 1);
+    }
+    assert_eq!(Source::from_iter(vec![1]).connect(sink), 5001);
+}
@@ -42,6 +42,17 @@
 //!    ready to be read by the next call to `consume`.
 //! 3. `defer` introduces a point of lazyiness, artifically deferring all further actions.
 //!
+//! The simplest conduit built from these lifts a plain function,
+//! applying it to every value from upstream:
+//!
+//! ```
+//! use std::iter::FromIterator;
+//! use plumbum::*;
+//!
+//! let src = Source::from_iter(vec![1, 2, 3]).fuse(map_conduit(|x| x * 2));
+//! assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![2, 4, 6]);
+//! ```
+//!
 //! ## Example
 //!
 //! ```
@@ -84,16 +95,64 @@
 //!     let res = source().fuse(conduit()).connect(sink());
 //!     assert_eq!(res, "(1,2):(2,3):(3,4):...")
 //! }
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(feature = "metrics")]
+extern crate metrics;
+
+#[cfg(feature = "regex")]
+extern crate regex;
+
+#[cfg(feature = "csv")]
+extern crate csv;
+
+#[cfg(feature = "csv")]
+extern crate serde;
+
+#[cfg(feature = "derive")]
+extern crate plumbum_derive;
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::convert::Infallible;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::mem::{replace, swap};
 use std::iter::{Extend, FromIterator};
+use std::ops::{Add, Shr};
+
+#[cfg(feature = "tracing")]
+macro_rules! trace_step {
+    ($step: tt, $input: ty, $output: ty) => (
+        tracing::trace!(
+            input = ::std::any::type_name::<$input>(),
+            output = ::std::any::type_name::<$output>(),
+            $step
+        )
+    )
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_step {
+    ($step: tt, $input: ty, $output: ty) => (())
+}
 
 mod chunk;
 pub use chunk::Chunk;
 
+mod iter;
+pub use iter::{SourceIter, DynSource, Peekable};
+
 /// Interfacing with `std::io`.
 pub mod io;
 
+/// Commonly used sources, conduits and sinks.
+pub mod extra;
+
+/// Type classes describing the algebraic structure of conduits.
+pub mod algebra;
+
 mod kleisli;
 pub use kleisli::Kleisli;
 
@@ -103,6 +162,26 @@ pub use pipe::*;
 mod fuse;
 pub use fuse::*;
 
+mod driver;
+pub use driver::Driver;
+
+mod mem;
+pub use mem::{MemLimitError, limit_mem};
+
+mod pipeline;
+pub use pipeline::{Pipeline, conduit_thread, connect_safe, pipeline};
+
+#[cfg(feature = "derive")]
+pub use plumbum_derive::Conduit;
+
+#[cfg(feature = "tracing")]
+mod trace;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "async")]
+pub use asynchronous::{AsyncSource, AsyncSink, connect_async, tokio_receiver_source, tokio_sender_sink};
+
 pub enum Void {}
 
 /// Represents a conduit, i.e. a sequence of await/yield actions.
@@ -112,6 +191,9 @@ pub enum Void {}
 /// - `A` is the return type of the conduit.
 pub enum ConduitM<'a, I, O, A> {
     /// The case `Pure(a)` means that the conduit contains no further actions and just returns the result `a`.
+    ///
+    /// The result is boxed because continuations pass it around type-erased as a `Box<()>`.
+    /// For zero-sized results such as `()`, the box does not allocate.
     Pure(Box<A>),
     /// The case `Defer(k)` means that the conduit needs another iteration to make progress,
     /// and the remaining (suspended) program is given by the kleisli arrow `k`
@@ -130,6 +212,21 @@ pub enum ConduitM<'a, I, O, A> {
     Leftover(Vec<I>, Kleisli<'a, (), I, O, A>)
 }
 
+/// A boxed continuation of a conduit that returns `A`, as taken by `ConduitM::and_then_all`.
+pub type Continuation<'a, I, O, A, B> = Box<dyn FnOnce(A) -> ConduitM<'a, I, O, B> + 'a>;
+
+/// A `ConduitM` that only captures `'static` data, e.g. to store it without a lifetime parameter.
+pub type StaticConduitM<I, O, A> = ConduitM<'static, I, O, A>;
+
+/// A `Source` that only captures `'static` data.
+pub type StaticSource<O> = Source<'static, O>;
+
+/// A `Conduit` that only captures `'static` data.
+pub type StaticConduit<I, O> = Conduit<'static, I, O>;
+
+/// A `Sink` that only captures `'static` data.
+pub type StaticSink<I, A> = Sink<'static, I, A>;
+
 /// Provides a stream of output values,
 /// without consuming any input or producing a final result.
 pub type Source<'a, O> = ConduitM<'a, Void, O, ()>;
@@ -171,52 +268,225 @@ impl<'a, O> ConduitM<'a, Void, O, ()> {
     ///
     /// assert_eq!(src.connect(sink), 85);
     /// ```
-    pub fn connect<A>(mut self, mut sink: Sink<'a, O, A>) -> A where O: 'static {
+    pub fn connect<A>(self, sink: Sink<'a, O, A>) -> A where O: 'static {
+        let mut step = drive_pipeline(self, sink, no_input);
         loop {
-            let (next_src, next_sink) = match sink {
-                ConduitM::Pure(a) => {
-                    return *a;
+            step = match step {
+                Drive::Done(_, a) => return a,
+                Drive::Ready(src, sink) => drive_pipeline(src, sink, no_input),
+                Drive::Suspend(src, sink) => drive_pipeline(src, sink, no_input)
+            };
+        }
+    }
+
+    /// Counts the number of values produced by the source.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::Source;
+    ///
+    /// assert_eq!(Source::from_iter(vec![42, 43, 44]).count(), 3);
+    /// ```
+    pub fn count(self) -> usize where O: 'static {
+        self.connect(extra::count())
+    }
+
+    /// Tests whether any value produced by the source satisfies the predicate.
+    ///
+    /// Stops pulling from the source as soon as a matching value is found.
+    pub fn any<F>(self, pred: F) -> bool
+        where O: 'static, F: 'a + FnMut(&O) -> bool {
+        self.connect(extra::any(pred))
+    }
+
+    /// Tests whether all values produced by the source satisfy the predicate.
+    ///
+    /// Stops pulling from the source as soon as a non-matching value is found.
+    pub fn all<F>(self, pred: F) -> bool
+        where O: 'static, F: 'a + FnMut(&O) -> bool {
+        self.connect(extra::all(pred))
+    }
+
+    /// Returns the first value produced by the source that satisfies the predicate.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::Source;
+    ///
+    /// let src = Source::from_iter(vec![1, 2, 3, 4]);
+    /// assert_eq!(src.find(|x| x % 2 == 0), Some(2));
+    /// ```
+    pub fn find<F>(self, pred: F) -> Option<O>
+        where O: 'static, F: 'a + FnMut(&O) -> bool {
+        self.connect(extra::find(pred))
+    }
+
+    /// For each value `o` produced by the source, runs the source `f(o)`
+    /// to completion and produces its values paired with `o`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::Source;
+    ///
+    /// let src = Source::from_iter(vec![1, 2]).flat_zip(|n| Source::from_iter(0..n));
+    /// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![(1, 0), (2, 0), (2, 1)]);
+    /// ```
+    pub fn flat_zip<P, F>(self, f: F) -> ConduitM<'a, Void, (O, P), ()>
+        where O: 'static + Clone, P: 'static, F: 'a + FnMut(O) -> ConduitM<'a, Void, P, ()> {
+        self.fuse(flat_zip_with(f))
+    }
+
+    /// Connects the source to the sink returned by `f`, which is given
+    /// a reference to the source to choose the sink at runtime.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::{Source, Sink};
+    ///
+    /// let src = Source::from_iter(vec![42, 43]);
+    /// let sum = src.then_connect(|src| if src.is_pure() {
+    ///     Sink::fold(-1, |_, _| 0)
+    /// } else {
+    ///     Sink::fold(0, |x, y| x + y)
+    /// });
+    /// assert_eq!(sum, 85);
+    /// ```
+    pub fn then_connect<A, F>(self, f: F) -> A
+        where O: 'static, F: FnOnce(&ConduitM<'a, Void, O, ()>) -> Sink<'a, O, A> {
+        let sink = f(&self);
+        self.connect(sink)
+    }
+
+    /// Connects the source to a sink that consumes vectors of `chunk_size` values,
+    /// as `self.fuse(extra::chunk(chunk_size)).connect(sink)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::{Source, Sink};
+    ///
+    /// let sink = Sink::fold(0, |n, v: Vec<i32>| n + v.len() * v.len());
+    /// assert_eq!(Source::from_iter(vec![1, 2, 3]).chunked_connect(2, sink), 5);
+    /// ```
+    pub fn chunked_connect<B>(self, chunk_size: usize, sink: Sink<'a, Vec<O>, B>) -> B where O: 'static {
+        self.fuse(extra::chunk(chunk_size)).connect(sink)
+    }
+
+    /// Collects the values produced by the source, like `Iterator::collect`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::collections::BTreeSet;
+    /// use std::iter::FromIterator;
+    /// use plumbum::Source;
+    ///
+    /// let set: BTreeSet<i32> = Source::from_iter(vec![2, 1, 2]).collect();
+    /// assert_eq!(set.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+    /// ```
+    pub fn collect<C: FromIterator<O>>(self) -> C where O: 'static {
+        self.into_iter().collect()
+    }
+
+}
+
+/// The state of a pipeline after it has been driven by a single step.
+///
+/// The source of the pipeline may be any conduit that is never given
+/// any input, so that its result can be kept, as in `run_with_results`.
+enum Drive<'a, I, O, R, A> {
+    /// The sink has returned a result. The source is left as it is.
+    Done(ConduitM<'a, I, O, R>, A),
+    /// The pipeline can make further progress right away.
+    Ready(ConduitM<'a, I, O, R>, Sink<'a, O, A>),
+    /// One side of the pipeline has deferred while waiting for the other,
+    /// which is a good point to give control back to a scheduler.
+    Suspend(ConduitM<'a, I, O, R>, Sink<'a, O, A>)
+}
+
+thread_local! {
+    /// Whether the running step parked the task, i.e. registered it with
+    /// something that will wake it once it can make progress.
+    static PARKED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Records that the running step parked the task, see `connect_async`.
+fn park() {
+    PARKED.with(|parked| parked.set(true))
+}
+
+/// Returns whether the running step parked the task, and clears the record.
+fn take_parked() -> bool {
+    PARKED.with(|parked| parked.replace(false))
+}
+
+/// Drives a pipeline by a single step, pulling data from the source
+/// and pushing it into the sink.
+///
+/// This is shared between `connect` and `run_with_results`, which just keep
+/// on driving, and the asynchronous drivers, which suspend when asked to.
+/// When the source awaits, it is given the chunk returned by `idle`.
+fn drive_pipeline<'a, I: 'static, O: 'static, R: 'a, A>(src: ConduitM<'a, I, O, R>, sink: Sink<'a, O, A>,
+                                                   idle: fn() -> Chunk<Vec<I>>) -> Drive<'a, I, O, R, A> {
+    match sink {
+        ConduitM::Pure(a) => {
+            Drive::Done(src, *a)
+        },
+        ConduitM::Defer(k_sink) => {
+            Drive::Suspend(src, k_sink.run(()))
+        },
+        ConduitM::Flush(k_sink) => {
+            Drive::Ready(src, k_sink.run(()))
+        },
+        ConduitM::Await(k_sink) => {
+            trace_step!("connect: await", O, Void);
+            match src {
+                ConduitM::Pure(x) => {
+                    Drive::Ready(ConduitM::Pure(x), k_sink.run(Chunk::End))
                 },
-                ConduitM::Defer(k_sink) => {
-                    (self, k_sink.run(()))
+                ConduitM::Defer(k_src) => {
+                    Drive::Suspend(k_src.run(()), ConduitM::Await(k_sink))
                 },
-                ConduitM::Flush(k_sink) => {
-                    (self, k_sink.run(()))
+                ConduitM::Flush(k_src) => {
+                    Drive::Ready(k_src.run(()), k_sink.run(Chunk::Flush))
                 },
-                ConduitM::Await(k_sink) => {
-                    match self {
-                        ConduitM::Pure(x) => {
-                            (ConduitM::Pure(x), k_sink.run(Chunk::End))
-                        },
-                        ConduitM::Defer(k_src) => {
-                            (k_src.run(()), ConduitM::Await(k_sink))
-                        },
-                        ConduitM::Flush(k_src) => {
-                            (k_src.run(()), k_sink.run(Chunk::Flush))
-                        },
-                        ConduitM::Await(k_src) => {
-                            (k_src.run(Chunk::Chunk(Vec::new())), ConduitM::Await(k_sink))
-                        },
-                        ConduitM::Yield(o, k_src) => {
-                            (k_src.run(()), k_sink.run(Chunk::Chunk(o)))
-                        },
-                        ConduitM::Leftover(_, k_src) => {
-                            (k_src.run(()), ConduitM::Await(k_sink))
-                        }
-                    }
+                ConduitM::Await(k_src) => {
+                    Drive::Ready(k_src.run(idle()), ConduitM::Await(k_sink))
                 },
-                ConduitM::Yield(_, k_sink) => {
-                    (self, k_sink.run(()))
+                ConduitM::Yield(o, k_src) => {
+                    trace_step!("connect: yield", Void, O);
+                    Drive::Ready(k_src.run(()), k_sink.run(Chunk::Chunk(o)))
                 },
-                ConduitM::Leftover(o, k_sink) => {
-                    (ConduitM::Yield(o, Kleisli::from(move |_| self)), k_sink.run(()))
+                ConduitM::Leftover(_, k_src) => {
+                    Drive::Ready(k_src.run(()), ConduitM::Await(k_sink))
                 }
-            };
-            self = next_src;
-            sink = next_sink;
+            }
+        },
+        ConduitM::Yield(_, k_sink) => {
+            Drive::Ready(src, k_sink.run(()))
+        },
+        ConduitM::Leftover(o, k_sink) => {
+            Drive::Ready(ConduitM::Yield(o, Kleisli::from(move |_| src)), k_sink.run(()))
         }
     }
+}
 
+/// The chunk a source is given when it awaits in `connect`.
+fn no_input<I>() -> Chunk<Vec<I>> {
+    Chunk::Chunk(Vec::new())
 }
 
 /// Consumes a stream of input values and produces a stream of output values,
@@ -250,6 +520,7 @@ impl<'a, I, O> ConduitM<'a, I, O, ()> {
                 self.fuse(k.run(()))
             })),
             ConduitM::Yield(c, k) => ConduitM::Yield(c, Kleisli::from(move |_| {
+                trace_step!("fuse: yield", O, P);
                 self.fuse(k.run(()))
             })),
             ConduitM::Leftover(o, k) => ConduitM::Defer(Kleisli::from(move |_| {
@@ -266,12 +537,14 @@ impl<'a, I, O> ConduitM<'a, I, O, ()> {
                     k_left.run(()).fuse(k_right.run(Chunk::Flush))
                 })),
                 ConduitM::Yield(o, k_left) => ConduitM::Defer(Kleisli::from(move |_| {
+                    trace_step!("fuse: yield", I, O);
                     k_left.run(()).fuse(k_right.run(Chunk::Chunk(o)))
                 })),
                 ConduitM::Leftover(i, k_left) => ConduitM::Leftover(i, Kleisli::from(move |_| {
                     k_left.run(()).fuse(ConduitM::Await(k_right))
                 })),
                 ConduitM::Await(k_left) => ConduitM::Await(Kleisli::from(move |a| {
+                    trace_step!("fuse: await", I, O);
                     k_left.run(a).fuse(ConduitM::Await(k_right))
                 }))
             }
@@ -288,6 +561,40 @@ impl<'a, I, O> ConduitM<'a, I, O, ()> {
 
 }
 
+/// Fuses two conduits, so that `a >> b` is the same as `a.fuse(b)`.
+///
+/// Fusing a source all the way into a sink results in a closed pipeline,
+/// which can then be executed using `run`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Conduit, Source, Sink};
+///
+/// let src = Source::from_iter(vec![42, 43]);
+/// let pipeline = src >> Conduit::transform(|x| 1 + x) >> Sink::fold(0, |x, y| x + y);
+/// assert_eq!(pipeline.run(), 87);
+/// ```
+impl<'a, I: 'static, O: 'static, P: 'static, A: 'a> Shr<ConduitM<'a, O, P, A>> for ConduitM<'a, I, O, ()> {
+    type Output = ConduitM<'a, I, P, A>;
+
+    fn shr(self, other: ConduitM<'a, O, P, A>) -> ConduitM<'a, I, P, A> {
+        self.fuse(other)
+    }
+}
+
+/// A closed pipeline, which neither consumes input nor produces output.
+impl<'a, A: 'a> ConduitM<'a, Void, Void, A> {
+
+    /// Runs the pipeline to completion and returns its result.
+    pub fn run(self) -> A {
+        let src: Source<'a, Void> = ().into();
+        src.connect(self)
+    }
+
+}
+
 impl<'a, I, O: 'a> Extend<O> for ConduitM<'a, I, O, ()> {
     fn extend<T: IntoIterator<Item=O>>(&mut self, iterator: T)
         where I: 'a, T::IntoIter: 'a {
@@ -303,6 +610,90 @@ impl<'a, I, O: 'a> FromIterator<O> for ConduitM<'a, I, O, ()> {
     }
 }
 
+/// Concatenates two sources, so that `a + b` produces
+/// all values of `a`, followed by all values of `b`.
+///
+/// Each `+` appends to the continuations of the left source, so building a long
+/// source by adding to it in a loop, as in `src = src + s`, gets slower with every
+/// step, and panics in debug builds after 10000 steps. Use `concat` to chain many
+/// sources, or `Source::from_iter` to produce many values, instead.
+///
+/// Sources form a monoid under `+`: it is associative, and `mempty()`,
+/// which is also the `Default` source, is its identity.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::Source;
+///
+/// let src: Source<i32> = Source::from(vec![1, 2]) + Source::from(vec![3]);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+/// ```
+impl<'a, O: 'a> Add for ConduitM<'a, Void, O, ()> {
+    type Output = ConduitM<'a, Void, O, ()>;
+
+    fn add(self, other: ConduitM<'a, Void, O, ()>) -> ConduitM<'a, Void, O, ()> {
+        self.and(other)
+    }
+}
+
+impl<'a, O: 'a> From<Vec<O>> for ConduitM<'a, Void, O, ()> {
+    /// Creates a source that produces the elements of the vector.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::Source;
+    ///
+    /// let src: Source<i32> = vec![42, 43].into();
+    /// assert_eq!(src.count(), 2);
+    /// ```
+    fn from(v: Vec<O>) -> ConduitM<'a, Void, O, ()> {
+        ConduitM::from_iter(v)
+    }
+}
+
+impl<'a, O: 'static> From<ConduitM<'a, Void, O, ()>> for ConduitM<'a, (), O, ()> {
+    /// Turns a source into a conduit that ignores its input, via `to_producer`.
+    ///
+    /// There is no matching conversion for sinks, since `Sink<I, ()>`
+    /// already is the same type as `Conduit<I, Void>`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::{Source, Conduit};
+    ///
+    /// let conduit: Conduit<(), i32> = Source::from_iter(vec![42, 43]).into();
+    /// let src = Source::from_iter(vec![()]).fuse(conduit);
+    /// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![42, 43]);
+    /// ```
+    fn from(source: ConduitM<'a, Void, O, ()>) -> ConduitM<'a, (), O, ()> {
+        source.to_producer()
+    }
+}
+
+impl<'a, O: 'static> ConduitM<'a, (), O, ()> {
+
+    /// Turns a conduit over unit input into a source.
+    /// The conduit sees an exhausted upstream whenever it awaits input.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::{Conduit, produce};
+    ///
+    /// let conduit: Conduit<(), i32> = produce(42).and(produce(43));
+    /// assert_eq!(conduit.as_source().into_iter().collect::<Vec<_>>(), vec![42, 43]);
+    /// ```
+    pub fn as_source(self) -> Source<'a, O> {
+        let upstream: Source<'a, ()> = ().into();
+        upstream.fuse(self)
+    }
+
+}
+
 /// Consumes a stream of input values and produces a final result,
 /// without producing any output.
 pub type Sink<'a, I, A> = ConduitM<'a, I, Void, A>;
@@ -331,9 +722,9 @@ impl<'a, I, A> ConduitM<'a, I, Void, A> {
         }
     }
 
-    fn sink<F>(a: A, f: F) -> Self
-        where I: 'a, A: 'a, F: 'a + Fn(A, I) -> Result<A, A> {
-        consume().and_then(|io| {
+    fn sink<F>(a: A, mut f: F) -> Self
+        where I: 'a, A: 'a, F: 'a + FnMut(A, I) -> Result<A, A> {
+        consume().and_then(move |io| {
             match io {
                 None => a.into(),
                 Some(is) => match f(a, is) {
@@ -370,6 +761,7 @@ impl<'a, I, O, A> ConduitM<'a, I, O, A> {
     /// given a function from `A` to `ConduitM<I, O, B>`,
     /// passes the return value of the conduit to the function,
     /// and returns the resulting program.
+    #[inline]
     pub fn and_then<B, F>(self, js: F) -> ConduitM<'a, I, O, B>
         where F: 'a + FnOnce(A) -> ConduitM<'a, I, O, B> {
         match self {
@@ -382,6 +774,57 @@ impl<'a, I, O, A> ConduitM<'a, I, O, A> {
         }
     }
 
+    /// An alias for `and_then`.
+    #[inline]
+    pub fn flat_map<B, F>(self, f: F) -> ConduitM<'a, I, O, B>
+        where F: 'a + FnOnce(A) -> ConduitM<'a, I, O, B> {
+        self.and_then(f)
+    }
+
+    /// Appends a fallible continuation to a conduit. If the function
+    /// returns `Ok(next)`, continues with `next`, otherwise returns the error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::ConduitM;
+    ///
+    /// let c: ConduitM<(), (), &str> = "42".into();
+    /// let res = c.flat_map_result(|s| s.parse::<i32>().map(|x| ConduitM::from(x + 1)));
+    /// assert_eq!(res.unwrap_pure(), Ok(43));
+    /// ```
+    pub fn flat_map_result<B, E, F>(self, f: F) -> ConduitM<'a, I, O, Result<B, E>>
+        where B: 'a, E: 'a, F: 'a + FnOnce(A) -> Result<ConduitM<'a, I, O, B>, E> {
+        self.and_then(|a| {
+            match f(a) {
+                Ok(next) => next.map(Ok),
+                Err(e) => Err(e).into()
+            }
+        })
+    }
+
+    /// Appends each of the continuations in `fns` in turn, followed by `last`.
+    /// This allows building chains of continuations at runtime.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::{ConduitM, Continuation};
+    ///
+    /// let c: ConduitM<(), (), i32> = 1.into();
+    /// let fns: Vec<Continuation<(), (), i32, i32>> = vec![
+    ///     Box::new(|x| (x + 1).into()),
+    ///     Box::new(|x| (x * 10).into())
+    /// ];
+    /// let res = c.and_then_all(fns, Box::new(|x| x.to_string().into()));
+    /// assert_eq!(res, "20".to_string().into());
+    /// ```
+    pub fn and_then_all<B>(self, fns: Vec<Continuation<'a, I, O, A, A>>,
+                           last: Continuation<'a, I, O, A, B>) -> ConduitM<'a, I, O, B>
+        where I: 'a, O: 'a, A: 'a, B: 'a {
+        fns.into_iter().fold(self, |c, f| c.and_then(f)).and_then(last)
+    }
+
     /// Appends two conduits together, which means, it returns a new conduit that
     /// executes both conduits sequentially, and forwards the return value
     /// of the second.
@@ -401,22 +844,184 @@ impl<'a, I, O, A> ConduitM<'a, I, O, A> {
     /// Seen differently, it lifts a function from
     /// `A` to `B` into a function from `ConduitM<I, O, A>`
     /// to `ConduitM<I, O, B>`.
+    ///
+    /// Consecutive calls are not nested: each `map` on a suspended conduit
+    /// appends a single continuation to its arrow, and the arrow is run in a
+    /// loop, so a chain of `n` maps costs `O(n)` time but constant stack.
+    /// The functions are not fused, though, each one is still boxed and called
+    /// separately. Debug builds panic once an arrow holds more than 10000
+    /// continuations, so longer chains should be folded into a single `map`.
     pub fn map<B, F>(self, f: F) -> ConduitM<'a, I, O, B>
         where F: 'a + FnOnce(A) -> B {
         self.and_then(move |a| f(a).into())
     }
 
+    /// An alias for `map`, making explicit that it modifies
+    /// the return value, rather than the values passed downstream.
+    #[inline]
+    pub fn map_return<B, F>(self, f: F) -> ConduitM<'a, I, O, B>
+        where F: 'a + FnOnce(A) -> B {
+        self.map(f)
+    }
+
+    /// Discards the return value of the conduit.
+    pub fn void(self) -> ConduitM<'a, I, O, ()> {
+        self.map(|_| ())
+    }
+
+    /// Runs a side-effect on the return value of the conduit,
+    /// and passes the value on unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::{Source, Sink};
+    ///
+    /// let sink = Sink::fold(0, |x, y| x + y).inspect_return(|sum| println!("sum: {}", sum));
+    /// assert_eq!(Source::from_iter(vec![42, 43]).connect(sink), 85);
+    /// ```
+    pub fn inspect_return<F>(self, f: F) -> ConduitM<'a, I, O, A>
+        where F: 'a + FnOnce(&A) {
+        self.map(move |a| {
+            f(&a);
+            a
+        })
+    }
+
+    /// Returns `true` if the conduit is `Pure`, i.e. has no further actions.
+    pub fn is_pure(&self) -> bool {
+        matches!(self, ConduitM::Pure(_))
+    }
+
+    /// Returns `true` if the conduit is waiting for input from upstream.
+    pub fn is_await(&self) -> bool {
+        matches!(self, ConduitM::Await(_))
+    }
+
+    /// Returns `true` if the conduit is yielding output downstream.
+    pub fn is_yield(&self) -> bool {
+        matches!(self, ConduitM::Yield(_, _))
+    }
+
+    /// Returns bounds on the number of values the conduit yields downstream,
+    /// analogous to `Iterator::size_hint`. Sources created from a `Vec` or an iterator
+    /// carry the bounds of the iterator, so a `Vec` source reports its exact length.
+    /// Other conduits only count the chunk they are about to yield, and have
+    /// an upper bound only if it is their last chunk, e.g. with `produce_chunk`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::iter::FromIterator;
+    /// use plumbum::{ConduitM, Source, produce_chunk};
+    ///
+    /// let src: Source<i32> = vec![42, 43].into();
+    /// assert_eq!(src.size_hint(), (2, Some(2)));
+    /// let src: Source<i32> = produce_chunk(vec![42, 43]);
+    /// assert_eq!(src.size_hint(), (2, Some(2)));
+    /// let src = Source::from_iter(vec![42, 43]) + Source::from(vec![44]);
+    /// assert_eq!(src.size_hint(), (1, None));
+    /// let done: ConduitM<(), (), ()> = ().into();
+    /// assert_eq!(done.size_hint(), (0, Some(0)));
+    /// ```
+    pub fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            ConduitM::Pure(_) => (0, Some(0)),
+            ConduitM::Yield(o, k) => {
+                let (lower, upper) = kleisli::hint(k);
+                (lower.saturating_add(o.len()), upper.and_then(|n| n.checked_add(o.len())))
+            },
+            _ => (0, None)
+        }
+    }
+
+    /// Returns the result of a `Pure` conduit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the conduit is not `Pure`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::Conduit;
+    ///
+    /// let c: Conduit<(), ()> = ().into();
+    /// assert_eq!(c.unwrap_pure(), ());
+    /// ```
+    pub fn unwrap_pure(self) -> A {
+        let variant = match self {
+            ConduitM::Pure(a) => return *a,
+            ConduitM::Defer(_) => "Defer",
+            ConduitM::Flush(_) => "Flush",
+            ConduitM::Await(_) => "Await",
+            ConduitM::Yield(_, _) => "Yield",
+            ConduitM::Leftover(_, _) => "Leftover"
+        };
+        panic!("called `ConduitM::unwrap_pure()` on a `{}` value", variant)
+    }
+
+    /// Returns `Some(a)` if the conduit is `Pure(a)`, and `None` otherwise.
+    pub fn try_pure(self) -> Option<A> {
+        match self {
+            ConduitM::Pure(a) => Some(*a),
+            _ => None
+        }
+    }
+
     fn extend_iter<T: 'a + Iterator<Item=O>>(self, mut iterator: T) -> Self
         where I: 'a, O: 'a, A: 'a {
         self.and_then(|a| {
             let next = iterator.next();
             match next {
                 None => a.into(),
-                Some(x) => produce(x).and(a.into()).extend_iter(iterator)
+                Some(x) => {
+                    let hint = iterator.size_hint();
+                    match produce(x).and(a.into()).extend_iter(iterator) {
+                        ConduitM::Yield(o, k) => ConduitM::Yield(o, kleisli::with_hint(k, hint)),
+                        c => c
+                    }
+                }
+            }
+        })
+    }
+
+}
+
+impl<'a, I, O, A, E> ConduitM<'a, I, O, Result<A, E>> {
+
+    /// Appends a continuation to a conduit returning a `Result`,
+    /// which is only run if the conduit returns `Ok`.
+    /// If the conduit returns `Err`, the error is passed on as it is.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::ConduitM;
+    ///
+    /// let ok: ConduitM<(), (), Result<i32, String>> = Ok(42).into();
+    /// assert_eq!(ok.and_then_result(|x| Ok(x + 1).into()), Ok(43).into());
+    ///
+    /// let err: ConduitM<(), (), Result<i32, String>> = Err("boom".to_string()).into();
+    /// assert_eq!(err.and_then_result(|x| Ok(x + 1).into()), Err("boom".to_string()).into());
+    /// ```
+    pub fn and_then_result<B, F>(self, f: F) -> ConduitM<'a, I, O, Result<B, E>>
+        where E: 'a, F: 'a + FnOnce(A) -> ConduitM<'a, I, O, Result<B, E>> {
+        self.and_then(|res| {
+            match res {
+                Ok(a) => f(a),
+                Err(e) => Err(e).into()
             }
         })
     }
 
+    /// Passes the result of the conduit to the given function, whether it is `Ok` or `Err`.
+    pub fn with_result<B, F>(self, f: F) -> ConduitM<'a, I, O, B>
+        where F: 'a + FnOnce(Result<A, E>) -> B {
+        self.map(f)
+    }
+
 }
 
 impl<'a, I, O, A: PartialEq> PartialEq for ConduitM<'a, I, O, A> {
@@ -428,6 +1033,78 @@ impl<'a, I, O, A: PartialEq> PartialEq for ConduitM<'a, I, O, A> {
     }
 }
 
+/// Equality is only reflexive for `Pure` conduits, since conduits with pending
+/// actions never compare equal, so this does not fully meet the contract of `Eq`.
+/// It exists only so that `Pure` conduits can be used as keys of a `HashMap`
+/// or `HashSet`; conduits with pending actions must not be, and hashing one
+/// panics, see the implementation of `Hash` below.
+impl<'a, I, O, A: Eq> Eq for ConduitM<'a, I, O, A> {}
+
+/// Hashes the result of a `Pure` conduit, which together with
+/// `Eq` allows completed conduits to be used as map keys.
+///
+/// Conduits with pending actions are not values and must not be used as keys:
+/// hashing one panics.
+///
+/// # Example
+///
+/// ```rust
+/// use std::collections::HashSet;
+/// use plumbum::Conduit;
+///
+/// let done: Conduit<(), ()> = ().into();
+/// let mut set = HashSet::new();
+/// set.insert(done);
+/// assert_eq!(set.len(), 1);
+/// ```
+impl<'a, I, O, A: Hash> Hash for ConduitM<'a, I, O, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let variant = match self {
+            ConduitM::Pure(a) => return a.hash(state),
+            ConduitM::Defer(_) => "Defer",
+            ConduitM::Flush(_) => "Flush",
+            ConduitM::Await(_) => "Await",
+            ConduitM::Yield(_, _) => "Yield",
+            ConduitM::Leftover(_, _) => "Leftover"
+        };
+        panic!("cannot hash a `{}` conduit, only `Pure` values are hashable", variant)
+    }
+}
+
+/// Compares the results of `Pure` conduits. Conduits with pending
+/// actions are not comparable, neither with each other nor with `Pure` ones.
+impl<'a, I, O, A: PartialOrd> PartialOrd for ConduitM<'a, I, O, A> {
+    fn partial_cmp(&self, other: &ConduitM<'a, I, O, A>) -> Option<Ordering> {
+        match (self, other) {
+            (ConduitM::Pure(a), ConduitM::Pure(b)) => a.partial_cmp(b),
+            _ => None
+        }
+    }
+}
+
+/// Orders the results of `Pure` conduits, which allows completed conduits
+/// to be used as keys of a `BTreeMap`.
+///
+/// Like `Hash`, comparing a conduit with pending actions panics.
+///
+/// # Example
+///
+/// ```rust
+/// use std::collections::BTreeSet;
+/// use plumbum::ConduitM;
+///
+/// let set: BTreeSet<ConduitM<(), (), i32>> = vec![2.into(), 1.into()].into_iter().collect();
+/// assert_eq!(set.into_iter().next(), Some(1.into()));
+/// ```
+impl<'a, I, O, A: Ord> Ord for ConduitM<'a, I, O, A> {
+    fn cmp(&self, other: &ConduitM<'a, I, O, A>) -> Ordering {
+        match (self, other) {
+            (ConduitM::Pure(a), ConduitM::Pure(b)) => a.cmp(b),
+            _ => panic!("cannot order conduits with pending actions, only `Pure` values are ordered")
+        }
+    }
+}
+
 impl<'a, I, O, A: fmt::Debug> fmt::Debug for ConduitM<'a, I, O, A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -441,12 +1118,31 @@ impl<'a, I, O, A: fmt::Debug> fmt::Debug for ConduitM<'a, I, O, A> {
     }
 }
 
+impl<'a, I, O, A: fmt::Display> fmt::Display for ConduitM<'a, I, O, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConduitM::Pure(a) => write!(f, "{}", a),
+            ConduitM::Defer(_) => write!(f, "<deferred>"),
+            ConduitM::Flush(_) => write!(f, "<flushing>"),
+            ConduitM::Await(_) => write!(f, "<awaiting input>"),
+            ConduitM::Yield(_, _) => write!(f, "<yielding output>"),
+            ConduitM::Leftover(_, _) => write!(f, "<leftover input>")
+        }
+    }
+}
+
 impl<'a, I, O, A> From<A> for ConduitM<'a, I, O, A> {
     fn from(a: A) -> ConduitM<'a, I, O, A> {
         ConduitM::Pure(Box::new(a))
     }
 }
 
+impl<'a, I, O, A: Default> Default for ConduitM<'a, I, O, A> {
+    fn default() -> ConduitM<'a, I, O, A> {
+        ConduitM::Pure(Box::default())
+    }
+}
+
 /// Wait for a single input value from upstream.
 ///
 /// If no data is available, returns `None`.
@@ -490,6 +1186,93 @@ pub fn produce<'a, I, O>(o: O) -> ConduitM<'a, I, O, ()> {
     ConduitM::Yield(v, Kleisli::new())
 }
 
+/// A source that produces no values. This is the identity of `+` on sources.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, empty};
+///
+/// let src = empty() + Source::from_iter(vec![42]);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![42]);
+/// ```
+pub fn empty<'a, O>() -> Source<'a, O> {
+    ().into()
+}
+
+/// The identity of the monoid of sources under `+`, the same source as `empty()`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, mempty};
+///
+/// let src = Source::from_iter(vec![1, 2]) + mempty() + Source::from_iter(vec![3]);
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+/// let src: Source<i32> = Default::default();
+/// assert_eq!(src.into_iter().count(), 0);
+/// ```
+#[inline]
+pub fn mempty<'a, O>() -> Source<'a, O> {
+    empty()
+}
+
+/// A conduit that applies `f` to every value from upstream,
+/// and sends the result downstream.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, map_conduit};
+///
+/// let mut n = 0;
+/// let src = Source::from_iter(vec!['a', 'b']).fuse(map_conduit(move |c| { n += 1; (n, c) }));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![(1, 'a'), (2, 'b')]);
+/// ```
+pub fn map_conduit<'a, I: 'a, O: 'a, F>(mut f: F) -> Conduit<'a, I, O>
+    where F: 'a + FnMut(I) -> O {
+    consume().and_then(move |io| {
+        match io {
+            None => ().into(),
+            Some(i) => {
+                let o = f(i);
+                produce(o).and_then(move |_| map_conduit(f))
+            }
+        }
+    })
+}
+
+/// A source that produces a single value, like `std::iter::once`.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::once;
+///
+/// assert_eq!(once(42).into_iter().collect::<Vec<_>>(), vec![42]);
+/// ```
+pub fn once<'a, O: 'static>(val: O) -> Source<'a, O> {
+    produce(val)
+}
+
+/// An alias for `once`.
+#[inline]
+pub fn singleton<'a, O: 'static>(val: O) -> Source<'a, O> {
+    once(val)
+}
+
+/// A conduit that awaits input forever, ignoring everything it receives,
+/// and never produces a value or returns.
+///
+/// Running it to completion does not terminate, it is meant as a placeholder
+/// and as a base case in combinators that are expected to be cut short.
+pub fn never<'a, I: 'static, O: 'static, A>() -> ConduitM<'a, I, O, A> {
+    ConduitM::Await(Kleisli::from(|_| never()))
+}
+
 /// Defers a conduit action. Can be used to introduce artifical laziness.
 pub fn defer<'a, I, O>() -> ConduitM<'a, I, O, ()> {
     ConduitM::Defer(Kleisli::new())
@@ -508,3 +1291,299 @@ pub fn leftover<'a, I, O>(i: I) -> ConduitM<'a, I, O, ()> {
     v.push(i);
     ConduitM::Leftover(v, Kleisli::new())
 }
+
+/// Runs a conduit returning a function, then runs a second conduit,
+/// and applies the function to the return value of the latter.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, ConduitM, apply, consume};
+///
+/// let add = consume().map(|x: Option<i32>| move |y: Option<i32>| x.unwrap_or(0) + y.unwrap_or(0));
+/// let sink: ConduitM<_, _, i32> = apply(add, consume());
+/// assert_eq!(Source::from_iter(vec![42, 43]).connect(sink), 85);
+/// ```
+pub fn apply<'a, I: 'a, O: 'a, A: 'a, B, F>(func: ConduitM<'a, I, O, F>, arg: ConduitM<'a, I, O, A>)
+    -> ConduitM<'a, I, O, B> where F: 'a + FnOnce(A) -> B {
+    func.and_then(move |f| arg.map(f))
+}
+
+/// Runs `left` to completion, then `right`, and returns both results.
+/// This is the free-standing form of `ConduitM::zip`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink, consume, zip_return};
+///
+/// let sink = zip_return(consume(), Sink::fold(0, |x, y| x + y));
+/// assert_eq!(Source::from_iter(vec![1, 2, 3]).connect(sink), (Some(1), 5));
+/// ```
+pub fn zip_return<'a, I: 'a, O: 'a, A: 'a, B: 'a>(left: ConduitM<'a, I, O, A>, right: ConduitM<'a, I, O, B>)
+    -> ConduitM<'a, I, O, (A, B)> {
+    left.zip(right)
+}
+
+/// Pulls data from the source and pushes it into the sink, like `connect`,
+/// but returns the results of both the source and the sink.
+///
+/// The source never receives any input. If the sink terminates first,
+/// the source is still run to completion, and its remaining values are discarded.
+///
+/// # Example
+///
+/// ```rust
+/// use plumbum::{ConduitM, Sink, consume, produce, run_with_results};
+///
+/// let src: ConduitM<(), i32, usize> = produce(1).and(produce(2)).map(|_| 2);
+/// assert_eq!(run_with_results(src, Sink::fold(0, |x, y| x + y)), (2, 3));
+/// let src: ConduitM<(), i32, usize> = produce(1).and(produce(2)).map(|_| 2);
+/// assert_eq!(run_with_results(src, consume()), (2, Some(1)));
+/// ```
+pub fn run_with_results<'a, O: 'static, A: 'a, B>(source: ConduitM<'a, (), O, A>, sink: Sink<'a, O, B>) -> (A, B) {
+    let mut step = drive_pipeline(source, sink, end_of_input);
+    loop {
+        step = match step {
+            Drive::Done(src, b) => return (run_to_result(src), b),
+            Drive::Ready(src, sink) => drive_pipeline(src, sink, end_of_input),
+            Drive::Suspend(src, sink) => drive_pipeline(src, sink, end_of_input)
+        };
+    }
+}
+
+/// The chunk a source is given when it awaits in `run_with_results`.
+fn end_of_input<I>() -> Chunk<Vec<I>> {
+    Chunk::End
+}
+
+/// Runs a conduit without input to completion, discarding its output.
+fn run_to_result<'a, O: 'static, A>(conduit: ConduitM<'a, (), O, A>) -> A {
+    let mut conduit = conduit;
+    loop {
+        conduit = match conduit {
+            ConduitM::Pure(a) => return *a,
+            ConduitM::Defer(k) => k.run(()),
+            ConduitM::Flush(k) => k.run(()),
+            ConduitM::Await(k) => k.run(Chunk::End),
+            ConduitM::Yield(_, k) => k.run(()),
+            ConduitM::Leftover(_, k) => k.run(())
+        };
+    }
+}
+
+/// Runs the given conduits in sequence, collecting their return values.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, sequence, consume};
+///
+/// let sink = sequence(vec![consume(), consume(), consume()]);
+/// assert_eq!(Source::from_iter(vec![42, 43]).connect(sink), vec![Some(42), Some(43), None]);
+/// ```
+pub fn sequence<'a, I: 'a, O: 'a, A: 'a>(conduits: Vec<ConduitM<'a, I, O, A>>) -> ConduitM<'a, I, O, Vec<A>> {
+    let acc = Vec::with_capacity(conduits.len());
+    sequence_from(conduits.into_iter(), acc)
+}
+
+fn sequence_from<'a, I: 'a, O: 'a, A: 'a>(mut conduits: ::std::vec::IntoIter<ConduitM<'a, I, O, A>>, mut acc: Vec<A>)
+    -> ConduitM<'a, I, O, Vec<A>> {
+    match conduits.next() {
+        None => acc.into(),
+        Some(c) => c.and_then(move |a| {
+            acc.push(a);
+            sequence_from(conduits, acc)
+        })
+    }
+}
+
+/// Chains the given sources, producing all values of the first one,
+/// then all values of the second one, and so on.
+///
+/// Unlike adding the sources up with `+`, this takes constant time per source.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, concat};
+///
+/// let sources = (0..3).map(|i| Source::from_iter(vec![i, i])).collect();
+/// assert_eq!(concat(sources).into_iter().collect::<Vec<_>>(), vec![0, 0, 1, 1, 2, 2]);
+/// ```
+pub fn concat<'a, O: 'a>(sources: Vec<Source<'a, O>>) -> Source<'a, O> {
+    concat_from(sources.into_iter())
+}
+
+fn concat_from<'a, O: 'a>(mut sources: ::std::vec::IntoIter<Source<'a, O>>) -> Source<'a, O> {
+    match sources.next() {
+        None => ().into(),
+        Some(src) => src.and_then(move |_| concat_from(sources))
+    }
+}
+
+/// Runs the given conduits in sequence, combining their return values with `+`,
+/// starting from `M::default()`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink, concat_returns};
+///
+/// let sink = concat_returns(vec![Sink::fold(0, |n, _| n + 1), Sink::fold(0, |n, _| n + 1)]);
+/// assert_eq!(Source::from_iter(vec!['a', 'b', 'c']).connect(sink), 3);
+/// ```
+pub fn concat_returns<'a, I: 'a, O: 'a, M>(conduits: Vec<ConduitM<'a, I, O, M>>) -> ConduitM<'a, I, O, M>
+    where M: 'a + Default + Add<Output = M> {
+    concat_returns_from(conduits.into_iter(), M::default())
+}
+
+fn concat_returns_from<'a, I: 'a, O: 'a, M>(mut conduits: ::std::vec::IntoIter<ConduitM<'a, I, O, M>>, acc: M)
+    -> ConduitM<'a, I, O, M> where M: 'a + Add<Output = M> {
+    match conduits.next() {
+        None => acc.into(),
+        Some(c) => c.and_then(move |m| concat_returns_from(conduits, acc + m))
+    }
+}
+
+/// Runs the conduit created by `make` `n` times in sequence.
+/// Each run continues on the upstream values left by the previous one.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, consume, produce, repeat_n};
+///
+/// let src = Source::from_iter(vec![1, 2, 3]).fuse(repeat_n(2, || {
+///     consume().and_then(|io| match io {
+///         None => ().into(),
+///         Some(x) => produce(x * 10)
+///     })
+/// }));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![10, 20]);
+/// ```
+pub fn repeat_n<'a, I: 'a, O: 'a, F>(n: usize, make: F) -> ConduitM<'a, I, O, ()>
+    where F: 'a + Fn() -> ConduitM<'a, I, O, ()> {
+    if n == 0 {
+        ().into()
+    } else {
+        make().and_then(move |_| repeat_n(n - 1, make))
+    }
+}
+
+/// Runs the conduit created by `conduit` `n` times in sequence,
+/// discarding the results. This is Haskell's `replicateM_`, and the same as `repeat_n`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Sink, Source, consume, replicate_m_};
+///
+/// let sink = replicate_m_(2, || consume().void()).and(Sink::fold(0, |x, y| x + y));
+/// assert_eq!(Source::from_iter(vec![1, 2, 3, 4]).connect(sink), 7);
+/// ```
+#[inline]
+pub fn replicate_m_<'a, I: 'a, O: 'a, F>(n: usize, conduit: F) -> ConduitM<'a, I, O, ()>
+    where F: 'a + Fn() -> ConduitM<'a, I, O, ()> {
+    repeat_n(n, conduit)
+}
+
+/// Runs the conduit created by `make` over and over, e.g. to serve one
+/// request at a time from the same upstream.
+///
+/// The conduit never returns, which is reflected in its return type.
+/// It keeps running after upstream is exhausted, so it should be used where
+/// downstream decides when to stop, and `make` must not return a conduit
+/// that completes without awaiting or yielding.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, consume, produce, repeat_forever};
+/// use plumbum::extra::take_until;
+///
+/// let server = repeat_forever(|| consume().and_then(|io: Option<i32>| produce(io.map(|x| x * 2))));
+/// let src = Source::from_iter(vec![1, 2]).fuse(server.map(|never| match never {}))
+///     .fuse(take_until(|x: &Option<i32>| x.is_none()));
+/// assert_eq!(src.into_iter().collect::<Vec<_>>(), vec![Some(2), Some(4), None]);
+/// ```
+pub fn repeat_forever<'a, I: 'a, O: 'a, F>(make: F) -> ConduitM<'a, I, O, Infallible>
+    where F: 'a + Fn() -> ConduitM<'a, I, O, ()> {
+    make().and_then(move |_| repeat_forever(make))
+}
+
+/// Wraps the conduit so that each of its steps is reported on stderr,
+/// as `[tag] step: await` and so on. This is meant for tracking down
+/// deadlocks and unexpected early termination during development,
+/// and is a no-op in release builds.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink, trace_steps};
+///
+/// let sink = trace_steps(Sink::fold(0, |x, y| x + y), "sum");
+/// assert_eq!(Source::from_iter(vec![42, 43]).connect(sink), 85);
+/// ```
+pub fn trace_steps<'a, I: 'static, O: 'static, A: 'a>(conduit: ConduitM<'a, I, O, A>, tag: &'static str)
+    -> ConduitM<'a, I, O, A> {
+    if cfg!(debug_assertions) {
+        trace_steps_from(conduit, tag)
+    } else {
+        conduit
+    }
+}
+
+fn trace_steps_from<'a, I: 'static, O: 'static, A: 'a>(conduit: ConduitM<'a, I, O, A>, tag: &'static str)
+    -> ConduitM<'a, I, O, A> {
+    match conduit {
+        ConduitM::Pure(a) => {
+            eprintln!("[{}] step: pure", tag);
+            ConduitM::Pure(a)
+        },
+        ConduitM::Defer(k) => {
+            eprintln!("[{}] step: defer", tag);
+            ConduitM::Defer(Kleisli::from(move |_| trace_steps_from(k.run(()), tag)))
+        },
+        ConduitM::Flush(k) => {
+            eprintln!("[{}] step: flush", tag);
+            ConduitM::Flush(Kleisli::from(move |_| trace_steps_from(k.run(()), tag)))
+        },
+        ConduitM::Await(k) => {
+            eprintln!("[{}] step: await", tag);
+            ConduitM::Await(Kleisli::from(move |chunk| trace_steps_from(k.run(chunk), tag)))
+        },
+        ConduitM::Yield(o, k) => {
+            eprintln!("[{}] step: yield {} value(s)", tag, o.len());
+            ConduitM::Yield(o, Kleisli::from(move |_| trace_steps_from(k.run(()), tag)))
+        },
+        ConduitM::Leftover(i, k) => {
+            eprintln!("[{}] step: leftover {} value(s)", tag, i.len());
+            ConduitM::Leftover(i, Kleisli::from(move |_| trace_steps_from(k.run(()), tag)))
+        }
+    }
+}
+
+fn flat_zip_with<'a, O, P, F>(mut f: F) -> ConduitM<'a, O, (O, P), ()>
+    where O: 'static + Clone, P: 'static, F: 'a + FnMut(O) -> ConduitM<'a, Void, P, ()> {
+    consume().and_then(move |io: Option<O>| {
+        match io {
+            None => ().into(),
+            Some(o) => {
+                let key = o.clone();
+                f(o).to_producer()
+                    .fuse(Conduit::transform(move |p| (key.clone(), p)))
+                    .and(flat_zip_with(f))
+            }
+        }
+    })
+}
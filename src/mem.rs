@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::mem::size_of;
+
+use super::{ConduitM, Kleisli};
+
+#[derive(Clone, Copy)]
+enum Budget {
+    Remaining(usize),
+    Exceeded
+}
+
+thread_local! {
+    /// The budgets of the `limit_mem` steps running on this thread, innermost last.
+    static BUDGETS: RefCell<Vec<Budget>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records that a buffering combinator allocated room for `n` more values of type `T`,
+/// charging it to every `limit_mem` that is currently running.
+pub fn charge<T>(n: usize) {
+    let bytes = n.saturating_mul(size_of::<T>());
+    BUDGETS.with(|budgets| {
+        for budget in budgets.borrow_mut().iter_mut() {
+            *budget = match *budget {
+                Budget::Remaining(left) if bytes <= left => Budget::Remaining(left - bytes),
+                _ => Budget::Exceeded
+            };
+        }
+    })
+}
+
+/// The error returned by a conduit that exceeded its memory limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemLimitError {
+    /// The limit in bytes that was exceeded.
+    pub limit: usize
+}
+
+impl fmt::Display for MemLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "conduit exceeded its memory limit of {} bytes", self.limit)
+    }
+}
+
+impl Error for MemLimitError {}
+
+/// Limits the memory the conduit may allocate for buffering values,
+/// terminating it with a `MemLimitError` once it allocated more than `max_bytes`.
+///
+/// Allocations are reported by the buffering combinators in `extra`, such as
+/// `chunk`, `take_last` or `batch_by_sentinel`, and are counted while the
+/// conduit runs. The count is cumulative: buffers handed downstream are not
+/// credited back, so the limit bounds the total amount of data the conduit
+/// buffers, which makes it a safety valve against untrusted input rather
+/// than a measure of peak memory. When limits are nested, allocations are
+/// charged to all of them, so an inner limit never lifts an outer one.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, MemLimitError, limit_mem};
+/// use plumbum::extra::take_last;
+///
+/// let sink = limit_mem(take_last::<u64>(4), 16);
+/// assert_eq!(Source::from_iter(vec![1, 2]).connect(sink), Ok(vec![1, 2]));
+/// let sink = limit_mem(take_last::<u64>(4), 16);
+/// assert_eq!(Source::from_iter(vec![1, 2, 3]).connect(sink), Err(MemLimitError { limit: 16 }));
+/// let sink = limit_mem(limit_mem(take_last::<u64>(4), usize::MAX), 16);
+/// assert_eq!(Source::from_iter(vec![1, 2, 3]).connect(sink), Err(MemLimitError { limit: 16 }));
+/// ```
+pub fn limit_mem<'a, I: 'static, O: 'static, A: 'a>(conduit: ConduitM<'a, I, O, A>, max_bytes: usize)
+    -> ConduitM<'a, I, O, Result<A, MemLimitError>> {
+    limited(conduit, max_bytes, max_bytes)
+}
+
+fn limited<'a, I: 'static, O: 'static, A: 'a>(conduit: ConduitM<'a, I, O, A>, limit: usize, left: usize)
+    -> ConduitM<'a, I, O, Result<A, MemLimitError>> {
+    match conduit {
+        ConduitM::Pure(a) => Ok(*a).into(),
+        ConduitM::Defer(k) => ConduitM::Defer(Kleisli::from(move |_| {
+            step(limit, left, move || k.run(()))
+        })),
+        ConduitM::Flush(k) => ConduitM::Flush(Kleisli::from(move |_| {
+            step(limit, left, move || k.run(()))
+        })),
+        ConduitM::Await(k) => ConduitM::Await(Kleisli::from(move |chunk| {
+            step(limit, left, move || k.run(chunk))
+        })),
+        ConduitM::Yield(o, k) => ConduitM::Yield(o, Kleisli::from(move |_| {
+            step(limit, left, move || k.run(()))
+        })),
+        ConduitM::Leftover(i, k) => ConduitM::Leftover(i, Kleisli::from(move |_| {
+            step(limit, left, move || k.run(()))
+        }))
+    }
+}
+
+/// Runs a single step of a limited conduit with its budget installed.
+fn step<'a, I: 'static, O: 'static, A: 'a, F>(limit: usize, left: usize, f: F)
+    -> ConduitM<'a, I, O, Result<A, MemLimitError>> where F: FnOnce() -> ConduitM<'a, I, O, A> {
+    BUDGETS.with(|budgets| budgets.borrow_mut().push(Budget::Remaining(left)));
+    let next = f();
+    match BUDGETS.with(|budgets| budgets.borrow_mut().pop()) {
+        Some(Budget::Remaining(left)) => limited(next, limit, left),
+        _ => Err(MemLimitError { limit }).into()
+    }
+}
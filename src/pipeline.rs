@@ -0,0 +1,76 @@
+use std::panic::{self, AssertUnwindSafe, UnwindSafe};
+use std::thread::{self, JoinHandle};
+
+use super::{Source, Sink};
+
+/// A source connected to a sink, ready to be run.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink, Pipeline, pipeline};
+///
+/// let p: Pipeline<i32> = pipeline(Source::from_iter(vec![42, 43]), Sink::fold(0, |x, y| x + y));
+/// assert_eq!(p.run(), 85);
+/// ```
+pub struct Pipeline<'a, A> {
+    inner: Box<dyn FnOnce() -> A + 'a>
+}
+
+impl<'a, A> Pipeline<'a, A> {
+    /// Runs the pipeline to completion and returns the result of the sink.
+    pub fn run(self) -> A {
+        (self.inner)()
+    }
+}
+
+/// Connects the source to the sink, deferring execution until the pipeline is run.
+pub fn pipeline<'a, O: 'static, A: 'a>(source: Source<'a, O>, sink: Sink<'a, O, A>) -> Pipeline<'a, A> {
+    Pipeline { inner: Box::new(move || source.connect(sink)) }
+}
+
+/// Runs a pipeline on a new thread, and returns the handle to join it.
+///
+/// Conduits hold boxed closures that are not `Send`, so rather than the
+/// source and sink themselves, this takes functions that build them on
+/// the spawned thread.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink, conduit_thread};
+///
+/// let handle = conduit_thread(|| Source::from_iter(vec![42, 43]),
+///                             || Sink::fold(0, |x, y| x + y));
+/// assert_eq!(handle.join().unwrap(), 85);
+/// ```
+pub fn conduit_thread<O, A, F, G>(source: F, sink: G) -> JoinHandle<A>
+    where O: 'static, A: 'static + Send,
+          F: 'static + Send + FnOnce() -> Source<'static, O>,
+          G: 'static + Send + FnOnce() -> Sink<'static, O, A> {
+    thread::spawn(move || source().connect(sink()))
+}
+
+/// Connects the source to the sink like `Source::connect`, but catches any
+/// panic raised while running the pipeline and returns it as an `Err`.
+///
+/// Conduits hold boxed closures and are therefore never `UnwindSafe` themselves,
+/// so they are asserted to be. The `'static` bound keeps them from borrowing
+/// any state of the caller, but state they share through e.g. an `Rc<RefCell<T>>`
+/// can still be observed half-updated after a panic.
+///
+/// # Example
+///
+/// ```rust
+/// use std::iter::FromIterator;
+/// use plumbum::{Source, Sink, connect_safe};
+///
+/// let sink = Sink::fold(0, |x: i32, y: i32| if y < 0 { panic!("negative") } else { x + y });
+/// assert!(connect_safe(Source::from_iter(vec![1, -1]), sink).is_err());
+/// ```
+pub fn connect_safe<O, A>(source: Source<'static, O>, sink: Sink<'static, O, A>) -> thread::Result<A>
+    where O: 'static + UnwindSafe, A: UnwindSafe {
+    panic::catch_unwind(AssertUnwindSafe(move || source.connect(sink)))
+}
@@ -0,0 +1,48 @@
+use tracing::{self, Span, Level};
+
+use super::{ConduitM, Kleisli};
+
+impl<'a, I: 'static, O: 'static, A: 'a> ConduitM<'a, I, O, A> {
+
+    /// Runs every step of the conduit inside a new `tracing` span with the given name,
+    /// so that the span covers the conduit's entire execution.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use plumbum::extra::{from_vec, to_vec};
+    ///
+    /// let src = from_vec(vec![1, 2, 3]).with_span("numbers");
+    /// assert_eq!(src.connect(to_vec().with_span("collect")), vec![1, 2, 3]);
+    /// ```
+    pub fn with_span(self, name: &'static str) -> ConduitM<'a, I, O, A> {
+        self.in_span(tracing::span!(Level::TRACE, "conduit", name))
+    }
+
+    fn in_span(self, span: Span) -> ConduitM<'a, I, O, A> {
+        match self {
+            ConduitM::Pure(a) => ConduitM::Pure(a),
+            ConduitM::Defer(k) => ConduitM::Defer(Kleisli::from(move |_| {
+                let next = span.in_scope(|| k.run(()));
+                next.in_span(span)
+            })),
+            ConduitM::Flush(k) => ConduitM::Flush(Kleisli::from(move |_| {
+                let next = span.in_scope(|| k.run(()));
+                next.in_span(span)
+            })),
+            ConduitM::Await(k) => ConduitM::Await(Kleisli::from(move |chunk| {
+                let next = span.in_scope(|| k.run(chunk));
+                next.in_span(span)
+            })),
+            ConduitM::Yield(o, k) => ConduitM::Yield(o, Kleisli::from(move |_| {
+                let next = span.in_scope(|| k.run(()));
+                next.in_span(span)
+            })),
+            ConduitM::Leftover(i, k) => ConduitM::Leftover(i, Kleisli::from(move |_| {
+                let next = span.in_scope(|| k.run(()));
+                next.in_span(span)
+            }))
+        }
+    }
+
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "derive")]
+
+use std::iter::FromIterator;
+
+use plumbum::{Conduit, Sink, Source};
+
+#[derive(Conduit)]
+struct Sum<'a> {
+    #[source]
+    src: Source<'a, i32>,
+    #[conduit]
+    double: Conduit<'a, i32, i32>,
+    #[conduit]
+    inc: Conduit<'a, i32, i32>,
+    #[sink]
+    out: Sink<'a, i32, i32>
+}
+
+#[derive(Conduit)]
+pub struct Collect<'a, T: 'static + Clone> {
+    #[source]
+    pub src: Source<'a, T>,
+    #[sink]
+    pub out: Sink<'a, T, Vec<T>>,
+    pub name: &'static str
+}
+
+#[test]
+fn derive_fuses_conduits_in_order() {
+    let sum = Sum {
+        src: Source::from_iter(vec![1, 2, 3]),
+        double: Conduit::transform(|x| x * 2),
+        inc: Conduit::transform(|x| x + 1),
+        out: Sink::fold(0, |x, y| x + y)
+    };
+    assert_eq!(sum.run(), 15);
+}
+
+#[test]
+fn derive_supports_generics_and_unmarked_fields() {
+    let collect = Collect {
+        src: Source::from_iter(vec!['a', 'b']),
+        out: Sink::fold(Vec::new(), |mut v, x| { v.push(x); v }),
+        name: "chars"
+    };
+    assert_eq!(collect.name, "chars");
+    assert_eq!(collect.run(), vec!['a', 'b']);
+}
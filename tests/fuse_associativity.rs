@@ -0,0 +1,54 @@
+use std::iter::FromIterator;
+
+use proptest::prelude::*;
+
+use plumbum::{Conduit, Sink, Source, consume, leftover, produce};
+use plumbum::extra::map_accum_l;
+
+fn collect<'a>() -> Sink<'a, i64, Vec<i64>> {
+    Sink::fold(Vec::new(), |mut v, x| {
+        v.push(x);
+        v
+    })
+}
+
+fn pairs<'a>() -> Conduit<'a, i64, i64> {
+    // Emits the sum of adjacent pairs, making use of leftovers.
+    consume().zip(consume()).and_then(|res| {
+        match res {
+            (Some(x), Some(y)) => produce(x + y).and(leftover(y)).and(pairs()),
+            _ => ().into()
+        }
+    })
+}
+
+proptest! {
+
+    #[test]
+    fn fuse_transforms(v in prop::collection::vec(-1000i64..1000, 0..16), k in -10i64..10) {
+        let a = Conduit::transform(move |x| x + k);
+        let b = Conduit::transform(|x| x * 3);
+        let c = Conduit::transform(move |x| x - k);
+        let left = Source::from_iter(v.clone()).fuse(a).fuse(b).fuse(c);
+        let a = Conduit::transform(move |x| x + k);
+        let b = Conduit::transform(|x| x * 3);
+        let c = Conduit::transform(move |x| x - k);
+        let right = Source::from_iter(v).fuse(a.fuse(b.fuse(c)));
+        prop_assert_eq!(left.connect(collect()), right.connect(collect()));
+    }
+
+    #[test]
+    fn fuse_with_leftovers(v in prop::collection::vec(-1000i64..1000, 0..16)) {
+        let left = Source::from_iter(v.clone()).fuse(pairs()).fuse(map_accum_l(0, |s, x| (s + x, s + x)));
+        let right = Source::from_iter(v.clone()).fuse(pairs().fuse(map_accum_l(0, |s, x| (s + x, s + x))));
+        prop_assert_eq!(left.connect(collect()), right.connect(collect()));
+    }
+
+    #[test]
+    fn fuse_into_sink(v in prop::collection::vec(-1000i64..1000, 0..16)) {
+        let left = Source::from_iter(v.clone()).fuse(pairs()).connect(collect());
+        let right = Source::from_iter(v).connect(pairs().fuse(collect()));
+        prop_assert_eq!(left, right);
+    }
+
+}
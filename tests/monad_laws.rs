@@ -0,0 +1,58 @@
+use std::iter::FromIterator;
+
+use proptest::prelude::*;
+
+use plumbum::{ConduitM, Sink, Source, consume};
+
+type M<A> = ConduitM<'static, (), (), A>;
+
+fn f(x: i32) -> M<i64> {
+    (x as i64 * 2).into()
+}
+
+fn g(y: i64) -> M<String> {
+    y.to_string().into()
+}
+
+fn sink_f(x: Option<i32>) -> Sink<'static, i32, (Option<i32>, Option<i32>)> {
+    consume().map(move |y| (x, y))
+}
+
+fn sink_g(xy: (Option<i32>, Option<i32>)) -> Sink<'static, i32, Vec<Option<i32>>> {
+    consume().map(move |z| vec![xy.0, xy.1, z])
+}
+
+proptest! {
+
+    #[test]
+    fn left_identity(a in any::<i32>()) {
+        prop_assert_eq!(M::from(a).and_then(f), f(a));
+    }
+
+    #[test]
+    fn right_identity(a in any::<i32>()) {
+        prop_assert_eq!(M::from(a).and_then(M::<i32>::from), M::from(a));
+    }
+
+    #[test]
+    fn associativity(a in any::<i32>()) {
+        prop_assert_eq!(M::from(a).and_then(f).and_then(g),
+                        M::from(a).and_then(|x| f(x).and_then(g)));
+    }
+
+    #[test]
+    fn right_identity_connected(v in prop::collection::vec(any::<i32>(), 0..8)) {
+        let m = consume().and_then(|x: Option<i32>| ConduitM::from(x));
+        prop_assert_eq!(Source::from_iter(v.clone()).connect(m),
+                        Source::from_iter(v).connect(consume()));
+    }
+
+    #[test]
+    fn associativity_connected(v in prop::collection::vec(any::<i32>(), 0..8)) {
+        let left = consume().and_then(sink_f).and_then(sink_g);
+        let right = consume().and_then(|x| sink_f(x).and_then(sink_g));
+        prop_assert_eq!(Source::from_iter(v.clone()).connect(left),
+                        Source::from_iter(v).connect(right));
+    }
+
+}